@@ -0,0 +1,40 @@
+// `full_update` against `force_frame` on the worst case for diffing: every cell changes every
+// frame, the way `_stress_test` recolours the whole grid red, then green, then blue. this needs a
+// real Launchpad plugged in (there's nothing to time otherwise), and does nothing without one.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use lp::{grid_full, Color, ComplexColor, Framebuffer, Launchpad, LaunchpadDevice};
+
+fn frames() -> [Framebuffer; 3] {
+    [(127, 0, 0), (0, 127, 0), (0, 0, 127)].map(|(r, g, b)| {
+        let mut fb = Framebuffer::new();
+        for key in grid_full() {
+            fb.set(key, Color::Complex(ComplexColor::Rgb(r, g, b)));
+        }
+        fb
+    })
+}
+
+fn worst_case(c: &mut Criterion) {
+    let mut launchpad = match Launchpad::connect(|_, _| {}) {
+        Ok(launchpad) => launchpad,
+        Err(e) => {
+            eprintln!("no Launchpad to benchmark against: {}", e);
+            return;
+        }
+    };
+    let frames = frames();
+    let mut group = c.benchmark_group("worst case frame");
+    group.bench_function("full_update", |b| {
+        let mut frames = frames.iter().cycle();
+        b.iter(|| LaunchpadDevice::full_update(&mut launchpad, frames.next().unwrap()).unwrap())
+    });
+    group.bench_function("force_frame", |b| {
+        let mut frames = frames.iter().cycle();
+        b.iter(|| launchpad.force_frame(frames.next().unwrap()).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, worst_case);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+// a tiny 3x5 font for writing on the grid. there are only digits so far, which is all anything
+// has needed.
+
+use crate::{coords_to_key, Color, Coord, Framebuffer, X, Y};
+
+pub const WIDTH: u8 = 3;
+pub const HEIGHT: u8 = 5;
+
+// each row, from the top, is three bits with the leftmost column in the highest
+const DIGITS: [[u8; HEIGHT as usize]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// draw `c` with its top left corner at (`left`, `top`), in `color`. only the lit pixels are
+// drawn, and anything that would go off the edge is left off. returns false (having drawn
+// nothing) if there isn't a glyph for `c`.
+pub fn draw_char(fb: &mut Framebuffer, c: char, left: X, top: Y, color: Color) -> bool {
+    let glyph = match c.to_digit(10) {
+        Some(digit) => DIGITS[digit as usize],
+        None => return false,
+    };
+    for (row, bits) in glyph.iter().enumerate() {
+        for column in 0..WIDTH {
+            let (x, y) = (left + column, top.wrapping_sub(row as u8));
+            if bits & (0b100 >> column) != 0 && (1..=9).contains(&x) && (1..=9).contains(&y) {
+                fb.set(coords_to_key(Coord::new(x, y)), color);
+            }
+        }
+    }
+    true
+}
+
+// draw each character of `text` in turn, a column apart, returning how wide it all was
+pub fn draw_str(fb: &mut Framebuffer, text: &str, left: X, top: Y, color: Color) -> u8 {
+    let mut x = left;
+    for c in text.chars() {
+        if draw_char(fb, c, x, top, color) {
+            x += WIDTH + 1;
+        }
+    }
+    (x - left).saturating_sub(1)
+}
+
+// how wide `draw_str` would make `text`
+pub fn str_width(text: &str) -> u8 {
+    let glyphs = text.chars().filter(|c| c.is_ascii_digit()).count() as u8;
+    (glyphs * (WIDTH + 1)).saturating_sub(1)
+}
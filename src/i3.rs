@@ -0,0 +1,57 @@
+// where i3's workspaces live on the grid: five to a row from the top left, so 0-4 along the top
+// row, 5-9 below them, and 10-14 below those. also the commands for rearranging them. (nothing
+// here talks to i3 itself, that's up to the caller.)
+
+use crate::{Coord, Key, GRID_SIZE};
+
+pub const WORKSPACES: u8 = 15;
+const PER_ROW: u8 = 5;
+
+// the key for workspace `n`, if it has one
+pub fn workspace_to_key(n: u8) -> Option<Key> {
+    if n < WORKSPACES {
+        Some(Key::from(Coord::new(
+            1 + n % PER_ROW,
+            GRID_SIZE - n / PER_ROW,
+        )))
+    } else {
+        None
+    }
+}
+
+// which workspace `key` is for, if any
+pub fn key_to_workspace(key: Key) -> Option<u8> {
+    let Coord { x, y } = Coord::from(key);
+    if (1..=PER_ROW).contains(&x) && y <= GRID_SIZE && GRID_SIZE - y < WORKSPACES / PER_ROW {
+        Some((GRID_SIZE - y) * PER_ROW + x - 1)
+    } else {
+        None
+    }
+}
+
+// one side of `swap_outputs_command`: an output, the numbers of the workspaces on it, and the name
+// of the one that's showing
+pub struct OutputWorkspaces<'a> {
+    pub name: &'a str,
+    pub workspaces: &'a [i32],
+    pub visible: &'a str,
+}
+
+// the i3 command that moves every workspace on `old` over to `new` and vice versa, then shows the
+// same workspaces as before (now on each other's outputs), ending up focused on what was `new`'s
+pub fn swap_outputs_command(old: &OutputWorkspaces, new: &OutputWorkspaces) -> String {
+    let moves = |from: &OutputWorkspaces, to: &OutputWorkspaces| {
+        from.workspaces
+            .iter()
+            .map(|w| format!("workspace {}, move workspace to output {}", w, to.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "{}, {}, workspace {}, workspace {}",
+        moves(old, new),
+        moves(new, old),
+        old.visible,
+        new.visible
+    )
+}
@@ -1,28 +1,182 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, Write};
 use std::iter;
+use std::ops::Index;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
 use midir::{ConnectError, MidiInput, MidiOutput};
+use parking_lot::Mutex;
 use thiserror::Error;
 
+// log to `tracing` if the feature's enabled, and compile to nothing otherwise (so that the hot
+// paths don't even format their arguments)
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::trace!($($arg)*);
+    };
+}
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+pub mod font;
+pub mod i3;
+pub mod page;
+pub mod palette;
+pub mod tee;
+#[cfg(feature = "virtual")]
+pub mod terminal;
+
+// the `index`th Launchpad among `ports` (for either direction), or why there isn't one
+fn find_port<P>(
+    ports: Vec<P>,
+    name: impl Fn(&P) -> Option<String>,
+    index: usize,
+) -> Result<P, ConnectionError> {
+    if ports.is_empty() {
+        return Err(ConnectionError::NoPorts);
+    }
+    let names: Vec<String> = ports.iter().map(|p| name(p).unwrap_or_default()).collect();
+    let found = ports
+        .into_iter()
+        .zip(&names)
+        // .find(|(_, name)| name.contains("LPMiniMK3 MI"))
+        .filter(|(_, name)| name.contains("LPMiniMK3 DA"))
+        .nth(index);
+    match found {
+        Some((port, _)) => Ok(port),
+        None => Err(ConnectionError::NotFoundError(names)),
+    }
+}
+
 pub struct Launchpad {
     out_con: midir::MidiOutputConnection,
     _in_con: midir::MidiInputConnection<()>,
+    waiting: Waiting,
+    listeners: Listeners,
+    held: Arc<HeldKeys>,
+    echoes: Arc<Mutex<Echoes>>,
+    debounce: Arc<Mutex<Debounce>>,
+    orientation: Arc<Mutex<Orientation>>,
+    // which model it is, for `Message::parse`: the input thread needs it too
+    model: Arc<Mutex<Model>>,
+    stats: Stats,
     send_buf: Vec<u8>,
+    max_message_size: Option<usize>,
+    firmware_version: Option<[u8; 4]>,
+    capabilities: Option<Capabilities>,
+    restore_mode_on_drop: bool,
     complex_color_buf: Vec<(Key, ComplexColor)>,
     current: HashMap<Key, Color>,
+    // the frame being drawn between `begin_frame` and `commit_frame`
+    frame: Option<Framebuffer>,
+    state: StateWatch,
+}
+
+// where a `Launchpad` is in its life, for showing a connection indicator. `connect` doesn't return
+// until the Launchpad is in programmer mode, so a `Launchpad` starts out `Connected`, and the only
+// transitions are:
+//
+// - `Connected` -> `Disconnected`, when the MIDI backend fails to send something (which is how a
+//   Launchpad that's been unplugged shows up), or when the `Launchpad` is closed or dropped.
+// - `Disconnected` -> `Reconnecting`, when something is sent successfully again, since a failed
+//   send might only have been a hiccup, or the Launchpad might have been plugged back in.
+// - `Reconnecting` -> `Connected`, straight after, once it's been put back in programmer mode
+//   (which being unplugged takes it out of). if that send fails, it's `Disconnected` again.
+//
+// once it's closed or dropped, there's nothing left to send, so it stays `Disconnected`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Reconnecting,
+}
+
+// the current `ConnectionState`, and who to tell (with the old state and the new one) when it
+// changes, see `Launchpad::on_state_change`
+struct StateWatch {
+    state: ConnectionState,
+    observer: Option<Box<dyn FnMut(ConnectionState, ConnectionState) + Send>>,
+}
+
+impl StateWatch {
+    fn set(&mut self, state: ConnectionState) {
+        if state == self.state {
+            return;
+        }
+        debug!("connection state {:?} -> {:?}", self.state, state);
+        let old = std::mem::replace(&mut self.state, state);
+        if let Some(observer) = &mut self.observer {
+            observer(old, state);
+        }
+    }
+
+    // note down how a send on `out_con` went, and if it's the first to work since one failed, go
+    // through `Reconnecting` (see `ConnectionState`). only `Other` means the backend is in trouble:
+    // `InvalidData` is the bytes' fault, and says nothing about the connection.
+    fn sent<T>(
+        &mut self,
+        out_con: &mut midir::MidiOutputConnection,
+        result: Result<T, midir::SendError>,
+    ) -> Result<T, midir::SendError> {
+        match result {
+            Ok(_) if self.state == ConnectionState::Disconnected => {
+                self.set(ConnectionState::Reconnecting);
+                match out_con.send(&Command::SetProgrammerMode(true).to_bytes()) {
+                    Ok(()) => self.set(ConnectionState::Connected),
+                    Err(e) => {
+                        self.set(ConnectionState::Disconnected);
+                        return Err(e);
+                    }
+                }
+            }
+            Err(midir::SendError::Other(_)) => self.set(ConnectionState::Disconnected),
+            _ => {}
+        }
+        result
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ConnectionError {
     #[error("couldn't initialise MIDI backend")]
     MidiInitError(#[from] midir::InitError),
-    #[error("Launchpad was not found")]
-    NotFoundError,
+    #[error("there are no MIDI ports at all (is the MIDI backend working?)")]
+    NoPorts,
+    // with the names of all the ports that there were
+    #[error("Launchpad was not found among the MIDI ports {0:?}")]
+    NotFoundError(Vec<String>),
     #[error("error connecting to the Launchpad")]
     ConnectionError,
     #[error("error sending to the Launchpad")]
     SendError(#[from] midir::SendError),
+    #[error("message of {0} bytes is too large to send")]
+    MessageTooLarge(usize),
+    #[error("timed out waiting for a reply from the Launchpad")]
+    Timeout,
+    #[error("looping text never finishes scrolling")]
+    EndlessScroll,
+    #[error("not a complete MIDI message: {0:02x?}")]
+    MalformedMessage(Vec<u8>),
+    #[error("this Launchpad doesn't support {0}")]
+    Unsupported(&'static str),
+    #[cfg(feature = "image")]
+    #[error("couldn't load image")]
+    ImageError(#[from] image::ImageError),
 }
 
 impl From<midir::ConnectError<MidiOutput>> for ConnectionError {
@@ -37,21 +191,277 @@ impl From<midir::ConnectError<MidiInput>> for ConnectionError {
     }
 }
 
+// the grid is GRID_SIZE keys square, with a row of buttons along the top and a column down the
+// side, which makes 81 LEDs in all (including the logo). keys are numbered 10 * y + x, so they
+// all fit below KEY_COUNT.
+pub const GRID_SIZE: u8 = 8;
+pub const MAX_COLORS_PER_FRAME: usize = 81;
+pub const KEY_COUNT: usize = 100;
+
 pub type X = u8;
 pub type Y = u8;
-pub fn coords_to_key(x: X, y: Y) -> Key {
-    10 * y + x
+// there are two ways of giving a position as coordinates:
+//
+// - the Launchpad's own, which the plain `coords_to_key` uses: from 1, counting from the bottom
+//   left, with y going up. (1, 1) is key 11, (8, 8) is key 88, and (9, 9) is the logo.
+// - an image's (or an array of rows'), which the `_topleft` versions use: from 0, counting from
+//   the top left of the 8x8 grid, with y going down. (0, 0) is key 81, (7, 7) is key 18, and x = 8
+//   is the column of buttons down the side. the row of buttons along the top is above y = 0, so
+//   it can't be reached this way.
+//
+// so going from one to the other is `(x, y) -> (x + 1, 8 - y)`, and back again.
+pub fn coords_to_key(coord: Coord) -> Key {
+    Key(10 * coord.y + coord.x)
+}
+pub fn key_to_coords(key: Key) -> Coord {
+    Coord::new(key.0 % 10, key.0 / 10)
+}
+pub fn coords_to_key_topleft(x: X, y: Y) -> Key {
+    coords_to_key(Coord::new(x + 1, GRID_SIZE - y))
+}
+// `None` for the top row (and anything that isn't a key at all), since there's nowhere for it
+pub fn key_to_coords_topleft(key: Key) -> Option<(X, Y)> {
+    let Coord { x, y } = key_to_coords(key);
+    (is_valid_key(key) && y <= GRID_SIZE).then(|| (x - 1, GRID_SIZE - y))
+}
+
+// a key on the board, numbered the way the Launchpad does in programmer mode (see
+// `coords_to_key`). it's a type of its own so that it can't be mixed up with a coordinate, or any
+// other number, by accident: `Key::from` and `u8::from` go between the two when it's needed, like
+// for the bytes of a MIDI message.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key(pub u8);
+
+impl From<u8> for Key {
+    fn from(n: u8) -> Key {
+        Key(n)
+    }
+}
+
+impl From<Key> for u8 {
+    fn from(key: Key) -> u8 {
+        key.0
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// a position on the board, in the Launchpad's own coordinates (see `coords_to_key`)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Coord {
+    pub x: X,
+    pub y: Y,
+}
+
+impl Coord {
+    pub const fn new(x: X, y: Y) -> Coord {
+        Coord { x, y }
+    }
+}
+
+impl From<Key> for Coord {
+    fn from(key: Key) -> Coord {
+        key_to_coords(key)
+    }
+}
+
+impl From<Coord> for Key {
+    fn from(coord: Coord) -> Key {
+        coords_to_key(coord)
+    }
+}
+
+impl From<(X, Y)> for Coord {
+    fn from((x, y): (X, Y)) -> Coord {
+        Coord { x, y }
+    }
+}
+
+impl From<Coord> for (X, Y) {
+    fn from(coord: Coord) -> (X, Y) {
+        (coord.x, coord.y)
+    }
+}
+
+// whether there's an LED at `key`: the 8x8 grid, the buttons along the top/side, or the logo
+pub fn is_valid_key(key: Key) -> bool {
+    (11..=99).contains(&key.0) && key_to_coords(key).x != 0
 }
-pub fn key_to_coords(key: Key) -> (X, Y) {
-    (key % 10, key / 10)
+// which part of the board a key is in
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Region {
+    // the 8x8 grid
+    Grid,
+    // the row of buttons along the top (91-98)
+    Top,
+    // the column of buttons down the right (19-89)
+    Side,
+    // the logo in the top right corner (99), which lights up but can't be pressed
+    Logo,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeyInfo {
+    pub coord: Coord,
+    pub region: Region,
+}
+
+// everything there is to know about a key in one go, or `None` if there's no such key
+pub fn decode_key(key: Key) -> Option<KeyInfo> {
+    if !is_valid_key(key) {
+        return None;
+    }
+    let coord = key_to_coords(key);
+    let region = match (coord.x > GRID_SIZE, coord.y > GRID_SIZE) {
+        (false, false) => Region::Grid,
+        (false, true) => Region::Top,
+        (true, false) => Region::Side,
+        (true, true) => Region::Logo,
+    };
+    Some(KeyInfo { coord, region })
+}
+// like `coords_to_key`, but `None` if the coordinates are off the edge of the board
+pub fn checked_coords_to_key(coord: Coord) -> Option<Key> {
+    let edge = 1..=GRID_SIZE + 1;
+    (edge.contains(&coord.x) && edge.contains(&coord.y)).then(|| coords_to_key(coord))
+}
+
+// which kind of Launchpad is on the other end. only the Mini MK3 is actually supported so far,
+// but this is enough to translate between the others' key numbers and ours.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Model {
+    MiniMk3,
+    X,
+    ProMk3,
+    MiniMk2,
+}
+
+impl Model {
+    // from the family code in a `DeviceId`. the Mini MK2 doesn't answer device inquiries in a way
+    // we understand, and a bootloader could be any of them, so those are `None`.
+    pub fn from_family(family: u16) -> Option<Model> {
+        match family {
+            MINI_MK3_FAMILY => Some(Model::MiniMk3),
+            X_FAMILY => Some(Model::X),
+            PRO_MK3_FAMILY => Some(Model::ProMk3),
+            _ => None,
+        }
+    }
+}
+
+// the rest of the crate numbers keys the way the Mini MK3 does in programmer mode, which the X
+// and the Pro do too (although the Pro's extra buttons around the edge don't fit, so they're
+// `None`). the Mini MK2 counts 16 to a row from the top left instead, with the side buttons at
+// the end of each row; its top row buttons are CCs rather than notes, so they aren't covered here.
+pub fn device_key_to_logical(model: Model, raw: u8) -> Option<Key> {
+    match model {
+        Model::MiniMk3 | Model::X | Model::ProMk3 => is_valid_key(Key(raw)).then_some(Key(raw)),
+        Model::MiniMk2 => {
+            let (row, column) = (raw / 16, raw % 16);
+            (row < 8 && column < 9).then(|| coords_to_key_topleft(column, row))
+        }
+    }
+}
+
+// the inverse of `device_key_to_logical`
+pub fn logical_to_device_key(model: Model, key: Key) -> Option<u8> {
+    if !is_valid_key(key) {
+        return None;
+    }
+    match model {
+        Model::MiniMk3 | Model::X | Model::ProMk3 => Some(key.0),
+        Model::MiniMk2 => key_to_coords_topleft(key).map(|(x, y)| 16 * y + x),
+    }
+}
+
+// turn a picture on the board a quarter turn clockwise/anticlockwise, or mirror it. the top row
+// of buttons and the column down the side don't have anywhere to go for some of these (there's
+// no row along the bottom or column down the left), so they become `None`. the logo isn't really
+// part of the picture, so it stays put.
+pub fn rotate_cw(key: Key) -> Option<Key> {
+    match key_to_coords(key) {
+        Coord { x: 9, y: 9 } => Some(key),
+        Coord { x, y } => checked_coords_to_key(Coord::new(y, 9 - x)),
+    }
+}
+pub fn rotate_ccw(key: Key) -> Option<Key> {
+    match key_to_coords(key) {
+        Coord { x: 9, y: 9 } => Some(key),
+        Coord { x, y } => checked_coords_to_key(Coord::new(9 - y, x)),
+    }
+}
+pub fn flip_horizontal(key: Key) -> Option<Key> {
+    match key_to_coords(key) {
+        Coord { x: 9, y: 9 } => Some(key),
+        Coord { x, y } => checked_coords_to_key(Coord::new(9 - x, y)),
+    }
+}
+pub fn flip_vertical(key: Key) -> Option<Key> {
+    match key_to_coords(key) {
+        Coord { x: 9, y: 9 } => Some(key),
+        Coord { x, y } => checked_coords_to_key(Coord::new(x, 9 - y)),
+    }
+}
+
+// how the Launchpad is physically mounted, e.g. `Rot90` for turned a quarter turn clockwise
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Orientation {
+    #[default]
+    Normal,
+    Rot90,
+    Rot180,
+    Rot270,
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl Orientation {
+    // where a key as the rest of the crate sees it actually is on the device (if anywhere: the
+    // edge buttons that get rotated off the board are `None`, and are never lit)
+    pub fn to_physical(self, key: Key) -> Option<Key> {
+        match self {
+            Orientation::Normal => Some(key),
+            Orientation::Rot90 => rotate_ccw(key),
+            Orientation::Rot180 => rotate_cw(key).and_then(rotate_cw),
+            Orientation::Rot270 => rotate_cw(key),
+            Orientation::FlipHorizontal => flip_horizontal(key),
+            Orientation::FlipVertical => flip_vertical(key),
+        }
+    }
+    // the inverse of `to_physical` (and edge buttons that don't map onto anything are ignored)
+    pub fn to_logical(self, key: Key) -> Option<Key> {
+        match self {
+            Orientation::Normal => Some(key),
+            Orientation::Rot90 => rotate_cw(key),
+            Orientation::Rot180 => rotate_ccw(key).and_then(rotate_ccw),
+            Orientation::Rot270 => rotate_ccw(key),
+            Orientation::FlipHorizontal => flip_horizontal(key),
+            Orientation::FlipVertical => flip_vertical(key),
+        }
+    }
+}
+
+// `a` and `b` as opposite corners of a rectangle, in whichever order, turned into the bottom left
+// and top right corners (which is what `rect` wants)
+pub fn rect_normalized(a: Key, b: Key) -> (Key, Key) {
+    let (a, b) = (key_to_coords(a), key_to_coords(b));
+    (
+        coords_to_key(Coord::new(a.x.min(b.x), a.y.min(b.y))),
+        coords_to_key(Coord::new(a.x.max(b.x), a.y.max(b.y))),
+    )
 }
 
 pub fn rect(a: Key, b: Key) -> impl Iterator<Item = Key> {
-    let (x0, y0) = key_to_coords(a);
-    let (x1, y1) = key_to_coords(b);
+    let Coord { x: x0, y: y0 } = key_to_coords(a);
+    let Coord { x: x1, y: y1 } = key_to_coords(b);
     assert!(x0 <= x1);
     assert!(y0 <= y1);
-    iter::successors(Some(key_to_coords(a)), move |(x, y)| {
+    iter::successors(Some((x0, y0)), move |(x, y)| {
         if *x == x1 {
             if *y == y1 {
                 None
@@ -62,18 +472,47 @@ pub fn rect(a: Key, b: Key) -> impl Iterator<Item = Key> {
             Some((x + 1, *y))
         }
     })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(|(x, y)| coords_to_key(Coord::new(x, y)))
 }
 
-pub type Key = u8;
+// the 64 keys of the 8x8 grid, without the buttons along the top/side
+pub fn grid_8x8() -> impl Iterator<Item = Key> {
+    rect(Key(11), Key(88))
+}
+
+// all 81 keys with LEDs: the grid, the buttons, and the logo
+pub fn grid_full() -> impl Iterator<Item = Key> {
+    rect(Key(11), Key(99))
+}
+
+// the smallest rectangle containing all of `keys`, as its bottom left and top right corners (so
+// it can go straight into `rect`), or `None` if there aren't any
+pub fn bounding_box(keys: impl Iterator<Item = Key>) -> Option<(Key, Key)> {
+    keys.map(key_to_coords)
+        .fold(None, |bounds, Coord { x, y }| match bounds {
+            None => Some((x, y, x, y)),
+            Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+        })
+        .map(|(x0, y0, x1, y1)| {
+            (
+                coords_to_key(Coord::new(x0, y0)),
+                coords_to_key(Coord::new(x1, y1)),
+            )
+        })
+}
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Simple(SimpleColor),
     Complex(ComplexColor),
 }
 
 impl Color {
+    // what a key shows when nothing's drawn on it. (not the same value as `Color::rgb(0, 0, 0)`,
+    // even though it looks the same!)
+    pub const OFF: Color = Color::simple(0);
+
     pub const fn simple(n: u8) -> Color {
         Color::Simple(SimpleColor::Static(n))
     }
@@ -86,9 +525,184 @@ impl Color {
     pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
         Color::Complex(ComplexColor::Rgb(r, g, b))
     }
+    pub fn is_off(&self) -> bool {
+        *self == Color::OFF
+    }
+    // what can go in a `SetColors` in place of this, when sending a whole frame at once (see
+    // `Launchpad::commit_frame`), or `None` if it needs a note message of its own. only static
+    // colours are swapped: flashing ones flash against whatever was there before, and pulsing ones
+    // have been seen to come out static when sent in the SysEx, so they keep to `KeyOn` (channel
+    // 3, i.e. 0x92) where pulsing is known to work.
+    pub fn batch_equivalent(self) -> Option<ComplexColor> {
+        match self {
+            Color::Simple(SimpleColor::Static(c)) => Some(ComplexColor::Static(c)),
+            Color::Simple(_) => None,
+            Color::Complex(c) => Some(c),
+        }
+    }
+}
+
+// the 8x8 grid as rows of colours, from the top left; see `Framebuffer::from_grid`
+pub type Grid = [[Color; GRID_SIZE as usize]; GRID_SIZE as usize];
+
+fn grid_cells(grid: &Grid) -> impl Iterator<Item = (Key, Color)> + '_ {
+    grid.iter().enumerate().flat_map(|(row, colors)| {
+        colors
+            .iter()
+            .enumerate()
+            .map(move |(col, color)| (coords_to_key_topleft(col as u8, row as u8), *color))
+    })
+}
+
+// the colour of every addressable LED (the 8x8 grid, the buttons along the top/side, and the logo)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Framebuffer {
+    cells: HashMap<Key, Color>,
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        let mut cells = HashMap::with_capacity(MAX_COLORS_PER_FRAME);
+        for key in grid_full() {
+            cells.insert(key, Color::OFF);
+        }
+        Framebuffer { cells }
+    }
+    // the 8x8 grid from an array of rows. note that this goes from the top left, like text does,
+    // so `grid[0][0]` is key 81 (not 11, as the device's own bottom-up numbering would have it).
+    // the buttons around the edge are left off.
+    pub fn from_grid(grid: &Grid) -> Framebuffer {
+        let mut fb = Framebuffer::new();
+        for (key, color) in grid_cells(grid) {
+            fb.set(key, color);
+        }
+        fb
+    }
+    pub fn get(&self, key: Key) -> Color {
+        self.cells[&key]
+    }
+    pub fn set(&mut self, key: Key, color: Color) {
+        *self.cells.get_mut(&key).unwrap() = color;
+    }
+    // set a cell to `color` at some fraction (0.0-1.0) of its full brightness, without touching
+    // the Launchpad's global brightness: for trails and fades and the like. RGB colours are scaled
+    // exactly, but palette colours can only be approximated by the nearest dimmer palette entry,
+    // which may well be a slightly different hue (or, near the bottom, just off).
+    pub fn set_with_brightness(&mut self, key: Key, color: Color, brightness: f32) {
+        let brightness = brightness.clamp(0.0, 1.0);
+        let dim_palette = |n: u8| {
+            let (r, g, b) = palette::to_rgb(n);
+            let scale = |c: u8| (c as f32 * brightness).round() as u8;
+            palette::nearest(scale(r), scale(g), scale(b))
+        };
+        let color = match color {
+            Color::Simple(SimpleColor::Static(n)) => {
+                Color::Simple(SimpleColor::Static(dim_palette(n)))
+            }
+            Color::Simple(SimpleColor::Flashing(n)) => {
+                Color::Simple(SimpleColor::Flashing(dim_palette(n)))
+            }
+            Color::Simple(SimpleColor::Pulsing(n)) => {
+                Color::Simple(SimpleColor::Pulsing(dim_palette(n)))
+            }
+            Color::Complex(ComplexColor::Static(n)) => {
+                Color::Complex(ComplexColor::Static(dim_palette(n)))
+            }
+            Color::Complex(ComplexColor::Flashing(a, b)) => {
+                Color::Complex(ComplexColor::Flashing(dim_palette(a), dim_palette(b)))
+            }
+            Color::Complex(ComplexColor::Pulsing(n)) => {
+                Color::Complex(ComplexColor::Pulsing(dim_palette(n)))
+            }
+            Color::Complex(ComplexColor::Rgb(r, g, b)) => {
+                let scale = |c: u8| (c as f32 * brightness).round() as u8;
+                Color::rgb(scale(r), scale(g), scale(b))
+            }
+        };
+        self.set(key, color);
+    }
+    pub fn clear(&mut self) {
+        for color in self.cells.values_mut() {
+            *color = Color::OFF;
+        }
+    }
+    pub fn as_map(&self) -> &HashMap<Key, Color> {
+        &self.cells
+    }
+    // set cells from 0-255 RGB values, gamma-corrected with `DEFAULT_GAMMA`
+    pub fn blit_rgb(&mut self, pixels: impl IntoIterator<Item = (Key, (u8, u8, u8))>) {
+        self.blit_rgb_with_gamma(pixels, DEFAULT_GAMMA);
+    }
+    pub fn blit_rgb_with_gamma(
+        &mut self,
+        pixels: impl IntoIterator<Item = (Key, (u8, u8, u8))>,
+        gamma: f32,
+    ) {
+        for (key, (r, g, b)) in pixels {
+            self.set(
+                key,
+                Color::rgb(
+                    gamma_correct(r, gamma),
+                    gamma_correct(g, gamma),
+                    gamma_correct(b, gamma),
+                ),
+            );
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer::new()
+    }
+}
+
+impl Index<Key> for Framebuffer {
+    type Output = Color;
+    fn index(&self, key: Key) -> &Color {
+        &self.cells[&key]
+    }
+}
+
+// a snapshot of everything the Launchpad is displaying, which can be put back later
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scene {
+    cells: HashMap<Key, Color>,
+}
+
+impl Scene {
+    pub fn get(&self, key: Key) -> Color {
+        self.cells[&key]
+    }
+}
+
+impl From<&Framebuffer> for Scene {
+    fn from(fb: &Framebuffer) -> Self {
+        Scene {
+            cells: fb.cells.clone(),
+        }
+    }
+}
+
+impl From<HashMap<Key, Color>> for Framebuffer {
+    fn from(cells: HashMap<Key, Color>) -> Self {
+        Framebuffer { cells }
+    }
+}
+
+// the LEDs' brightness is far from linear in the RGB values they're sent: 1 is barely visible,
+// and 20 is already quite bright. a gamma of 1.0 maps 0-255 linearly onto 0-127, and larger
+// values spread the low end out more (making dim colours visible).
+pub const DEFAULT_GAMMA: f32 = 2.2;
+
+// map a 0-255 channel value onto the device's 0-127 range, through a gamma curve
+pub fn gamma_correct(value: u8, gamma: f32) -> u8 {
+    ((value as f32 / 255.).powf(1. / gamma) * 127.).round() as u8
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SimpleColor {
     Static(u8),
     Flashing(u8),
@@ -96,6 +710,7 @@ pub enum SimpleColor {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ComplexColor {
     Static(u8),
     Flashing(u8, u8),
@@ -109,6 +724,29 @@ pub enum TextColor {
     Rgb(u8, u8, u8),
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Brightness(pub u8);
+
+impl Brightness {
+    // the levels that the device's own settings menu steps through, which aren't quite what you'd
+    // get by dividing 0-127 into 7 evenly: integer maths gives 90 and 108 instead of 91 and 109,
+    // and floating-point maths with rounding gives 73 instead of 72. hey novation: ????????
+    pub const STEPS: [u8; 8] = [0, 18, 36, 54, 72, 91, 109, 127];
+
+    // the step nearest to `fraction`, where 0.0 is off and 1.0 is the brightest
+    pub fn from_fraction(fraction: f32) -> Brightness {
+        let step = (fraction.clamp(0., 1.) * 7.).round() as usize;
+        Brightness(Brightness::STEPS[step])
+    }
+
+    // which of the steps this brightness is nearest to
+    pub fn step(&self) -> usize {
+        (0..Brightness::STEPS.len())
+            .min_by_key(|i| (Brightness::STEPS[*i] as i16 - self.0 as i16).abs())
+            .unwrap()
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Layout {
     Session,
@@ -132,6 +770,53 @@ impl From<&Layout> for u8 {
     }
 }
 
+// the other way round, for making sense of `Message::Layout`. anything this doesn't know about
+// (like the DAW faders layout) is handed back as it was.
+impl TryFrom<u8> for Layout {
+    type Error = u8;
+    fn try_from(value: u8) -> Result<Self, u8> {
+        match value {
+            0 => Ok(Layout::Session),
+            4 => Ok(Layout::Drums),
+            5 => Ok(Layout::Keys),
+            6 => Ok(Layout::User),
+            //0x0d => Ok(Layout::DawFaders),
+            0x7f => Ok(Layout::Programmer),
+            other => Err(other),
+        }
+    }
+}
+
+// which LEDs the Launchpad repeats back what they're showing for (as `Message::LedEcho`): the ones
+// it lit itself (`internal`, e.g. in the session layout), and the ones it was sent (`external`)
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LedFeedback {
+    pub internal: bool,
+    pub external: bool,
+}
+
+// the reply to `Command::GetLedFeedback` (`F0h 00h 20h 29h 02h 0Dh 0Ah <internal> <external>
+// F7h`), for anything that has the raw message. it's an error for it not to be that reply.
+impl TryFrom<&[u8]> for LedFeedback {
+    type Error = ();
+    fn try_from(message: &[u8]) -> Result<Self, ()> {
+        match *message {
+            [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, internal, external, 0xf7] => Ok(LedFeedback {
+                internal: internal == 1,
+                external: external == 1,
+            }),
+            _ => Err(()),
+        }
+    }
+}
+
+// which mode the Launchpad starts up in when it's plugged in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BootMode {
+    Live,
+    Programmer,
+}
+
 #[derive(Debug)]
 pub enum Command<'a> {
     GetVersions,
@@ -141,6 +826,19 @@ pub enum Command<'a> {
     GetProgrammerMode,
     KeyOn(Key, SimpleColor),
     KeyOff(Key),
+    // what `KeyOn` and `KeyOff` are made of, for when the lighting type isn't what you're after
+    // (e.g. in a layout other than programmer mode). the channel is 0-15, as it goes on the wire,
+    // so `KeyOn`'s static/flashing/pulsing are channels 0/1/2. nothing about these is checked
+    // against the grid, rotated by `set_orientation`, or noted down as what a key is showing.
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
     SetColors(&'a [(Key, ComplexColor)]),
     ScrollText {
         loops: Option<bool>,
@@ -152,11 +850,26 @@ pub enum Command<'a> {
     GetAwake,
     SetBrightness(u8),
     GetBrightness,
-    SetLedFeedback(bool, bool),
+    SetLedFeedback(LedFeedback),
     GetLedFeedback,
+    // Pro MK3 only: the scene launch buttons down the right (0 at the top), and the track select
+    // buttons along the bottom (0 on the left)
+    SetScene(u8, SimpleColor),
+    SetTrack(u8, SimpleColor),
+    // one MIDI clock pulse, of which there are 24 to a beat. flashing and pulsing colours keep time
+    // with whatever clock the Launchpad is sent (or go at 120bpm, if it isn't sent any), so this
+    // is how to set their tempo; see `Launchpad::set_tempo`.
+    TimingClock,
 }
 
 impl<'a> Command<'a> {
+    // the MIDI message for this command, e.g. for `Launchpad::send_prerendered`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.append_to_vec(&mut buf).unwrap();
+        buf
+    }
+
     fn append_to_vec(&self, buf: &mut Vec<u8>) -> io::Result<()> {
         match self {
             Command::GetVersions => buf.write_all(&[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]),
@@ -187,33 +900,51 @@ impl<'a> Command<'a> {
                 buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0e, 0xf7])
             }
             Command::KeyOn(key, color) => {
-                assert!(*key >= 11);
-                assert!(*key <= 99);
-                assert_ne!(*key % 10, 0);
-                match color {
-                    SimpleColor::Static(c) => buf.write_all(&[0x90, *key, *c]),
-                    SimpleColor::Flashing(c) => buf.write_all(&[0x91, *key, *c]),
-                    SimpleColor::Pulsing(c) => buf.write_all(&[0x92, *key, *c]),
+                assert!(is_valid_key(*key));
+                let (channel, velocity) = match *color {
+                    SimpleColor::Static(c) => (0, c),
+                    SimpleColor::Flashing(c) => (1, c),
+                    SimpleColor::Pulsing(c) => (2, c),
+                };
+                Command::NoteOn {
+                    channel,
+                    note: key.0,
+                    velocity,
                 }
+                .append_to_vec(buf)
             }
+            // a note on with no velocity, like the reference says, rather than a note off
             Command::KeyOff(key) => {
-                assert!(*key >= 11);
-                assert!(*key <= 99);
-                assert_ne!(*key % 10, 0);
-                buf.write_all(&[0x90, *key, 0])
+                assert!(is_valid_key(*key));
+                Command::NoteOn {
+                    channel: 0,
+                    note: key.0,
+                    velocity: 0,
+                }
+                .append_to_vec(buf)
+            }
+            Command::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => {
+                assert!(*channel < 16 && *note < 0x80 && *velocity < 0x80);
+                buf.write_all(&[0x90 | *channel, *note, *velocity])
+            }
+            Command::NoteOff { channel, note } => {
+                assert!(*channel < 16 && *note < 0x80);
+                buf.write_all(&[0x80 | *channel, *note, 0])
             }
             Command::SetColors(colors) => {
-                assert!(colors.len() <= 81);
+                assert!(colors.len() <= MAX_COLORS_PER_FRAME);
                 buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x03])?;
                 for (key, color) in colors.iter() {
-                    assert!(*key >= 11);
-                    assert!(*key <= 99);
-                    assert_ne!(*key % 10, 0);
+                    assert!(is_valid_key(*key));
                     match color {
-                        ComplexColor::Static(c) => buf.write_all(&[0, *key, *c])?,
-                        ComplexColor::Flashing(b, a) => buf.write_all(&[1, *key, *b, *a])?,
-                        ComplexColor::Pulsing(c) => buf.write_all(&[2, *key, *c])?,
-                        ComplexColor::Rgb(r, g, b) => buf.write_all(&[3, *key, *r, *g, *b])?,
+                        ComplexColor::Static(c) => buf.write_all(&[0, key.0, *c])?,
+                        ComplexColor::Flashing(b, a) => buf.write_all(&[1, key.0, *b, *a])?,
+                        ComplexColor::Pulsing(c) => buf.write_all(&[2, key.0, *c])?,
+                        ComplexColor::Rgb(r, g, b) => buf.write_all(&[3, key.0, *r, *g, *b])?,
                     }
                 }
                 buf.write_all(&[0xf7])?;
@@ -251,23 +982,85 @@ impl<'a> Command<'a> {
             Command::GetAwake => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x09, 0xf7]),
             Command::SetBrightness(brightness) => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x08, *brightness, 0xf7]),
             Command::GetBrightness => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x08, 0xf7]),
-            Command::SetLedFeedback(internal, external) => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, (*internal).into(), (*external).into(), 0xf7]),
+            Command::SetLedFeedback(LedFeedback { internal, external }) => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, (*internal).into(), (*external).into(), 0xf7]),
             Command::GetLedFeedback => buf.write_all(&[0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, 0xf7]),
+            Command::SetScene(index, color) | Command::SetTrack(index, color) => {
+                assert!(*index < 8);
+                let cc = if let Command::SetScene(..) = self {
+                    PRO_SCENE_CCS[*index as usize]
+                } else {
+                    PRO_TRACK_CCS[*index as usize]
+                };
+                match color {
+                    SimpleColor::Static(c) => buf.write_all(&[0xb0, cc, *c]),
+                    SimpleColor::Flashing(c) => buf.write_all(&[0xb1, cc, *c]),
+                    SimpleColor::Pulsing(c) => buf.write_all(&[0xb2, cc, *c]),
+                }
+            }
+            Command::TimingClock => buf.write_all(&[0xf8]),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Message {
     KeyDown(Key),
     KeyUp(Key),
     ApplicationVersion([u8; 4]),
     BootloaderVersion([u8; 4]),
+    // a device inquiry reply from anything other than a Mini MK3 (which sends one of the above),
+    // see `DeviceId`
+    DeviceId {
+        family: u16,
+        member: u16,
+        version: [u8; 4],
+    },
+    // the raw byte, since the Launchpad might be in a layout `Layout` doesn't cover; use
+    // `Layout::try_from` to interpret it
     Layout(u8),
     ProgrammerMode(bool),
+    // DAW mode being switched on or off, by a DAW (like Ableton) on the Launchpad's other port:
+    // `F0h 00h 20h 29h 02h 0Dh 10h <0/1> F7h`. while it's on, the DAW has the grid and nothing
+    // we send shows up, so this is the cue to stay out of the way, and then to take the grid back
+    // (with `SetProgrammerMode(true)`) once it's off again.
+    DawMode(bool),
     Awake(bool),
     Brightness(u8),
-    LedFeedback(bool, bool),
+    LedFeedback(LedFeedback),
+    TextScrollFinished,
+    // the Launchpad repeating back a colour we sent it, when LED feedback is on
+    LedEcho(Key, SimpleColor),
+    // Pro MK3 only (see `Message::parse`): a scene launch or track select button, and whether it
+    // was pressed (rather than released)
+    Scene(u8, bool),
+    Track(u8, bool),
+    // anything else at all, as it came: it's up to whoever gets it to decide whether it matters
+    Unknown(Vec<u8>),
+}
+
+// the CCs of the Pro MK3's scene launch buttons, which are a column down the right, top to bottom
+const PRO_SCENE_CCS: [u8; 8] = [89, 79, 69, 59, 49, 39, 29, 19];
+// and of its track select buttons, which are a row along the bottom, left to right
+const PRO_TRACK_CCS: [u8; 8] = [101, 102, 103, 104, 105, 106, 107, 108];
+
+impl Message {
+    // like `Message::from`, but knowing which model sent it. the only difference so far is that
+    // the Pro's scene launch and track select buttons become `Scene` and `Track`, both when
+    // they're pressed and when they're released, rather than `KeyDown`s and `KeyUp`s.
+    pub fn parse(model: Model, message: &[u8]) -> Message {
+        if model == Model::ProMk3 {
+            if let [0xb0, cc, velocity @ (0 | 127)] = *message {
+                let pressed = velocity == 127;
+                if let Some(i) = PRO_SCENE_CCS.iter().position(|c| *c == cc) {
+                    return Message::Scene(i as u8, pressed);
+                }
+                if let Some(i) = PRO_TRACK_CCS.iter().position(|c| *c == cc) {
+                    return Message::Track(i as u8, pressed);
+                }
+            }
+        }
+        Message::from(message)
+    }
 }
 
 impl From<&[u8]> for Message {
@@ -276,125 +1069,1201 @@ impl From<&[u8]> for Message {
         match *message {
             // accept either Note On or Control Change (the former for the 8x8 grid, the latter for
             // the buttons at the top/side)
-            [0x90 | 0xb0, note, 127] => KeyDown(note),
-            [0x90 | 0xb0, note, 0] => KeyUp(note),
+            [0x90 | 0xb0, note, 127] => KeyDown(Key(note)),
+            [0x90 | 0xb0, note, 0] => KeyUp(Key(note)),
+            // anything else on the note channels can only be an echo (although echoes of 0 and
+            // 127 look like the above, and have to be picked out by `Echoes::reinterpret`)
+            [0x90, note, color] => LedEcho(Key(note), SimpleColor::Static(color)),
+            [0x91, note, color] => LedEcho(Key(note), SimpleColor::Flashing(color)),
+            [0x92, note, color] => LedEcho(Key(note), SimpleColor::Pulsing(color)),
             [0xf0, 0x7e, 0x00, 0x06, 0x02, 0x00, 0x20, 0x29, 0x13, 0x01, 0x00, 0x00, a, b, c, d, 0xf7] => {
                 ApplicationVersion([a, b, c, d])
             }
             [0xf0, 0x7e, 0x00, 0x06, 0x02, 0x00, 0x20, 0x29, 0x13, 0x11, 0x00, 0x00, a, b, c, d, 0xf7] => {
                 BootloaderVersion([a, b, c, d])
             }
+            [0xf0, 0x7e, _, 0x06, 0x02, 0x00, 0x20, 0x29, f0, f1, m0, m1, a, b, c, d, 0xf7] => DeviceId {
+                family: u16::from_le_bytes([f0, f1]),
+                member: u16::from_le_bytes([m0, m1]),
+                version: [a, b, c, d],
+            },
             [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x00, layout, 0xf7] => Layout(layout),
             [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0e, mode, 0xf7] => ProgrammerMode(mode == 1),
+            [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x10, mode, 0xf7] => DawMode(mode == 1),
             [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x09, awake, 0xf7] => Awake(awake == 1),
             [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x08, brightness, 0xf7] => Brightness(brightness),
-            [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, internal, external, 0xf7] => {
-                LedFeedback(internal == 1, external == 1)
+            [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x0a, _, _, 0xf7] => {
+                LedFeedback(crate::LedFeedback::try_from(message).unwrap())
             }
-            _ => unimplemented!(),
+            [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d, 0x07, 0xf7] => TextScrollFinished,
+            // this is called on the MIDI input thread, so it mustn't panic, whatever turns up
+            _ => Unknown(message.to_vec()),
+        }
+    }
+}
+
+// which keys are currently held down. this is written from the MIDI input thread and read from
+// wherever, so it's just a bunch of atomics rather than anything that needs locking: each key is
+// always either definitely up or definitely down, but a look at several keys isn't a snapshot,
+// so a key pressed mid-look might show up or might not.
+#[derive(Debug)]
+pub struct HeldKeys([AtomicBool; KEY_COUNT]);
+
+impl HeldKeys {
+    pub fn new() -> HeldKeys {
+        HeldKeys(std::array::from_fn(|_| AtomicBool::new(false)))
+    }
+    // keep track of presses and releases in `message`, if there are any
+    pub fn update(&self, message: &Message) {
+        let (key, held) = match message {
+            Message::KeyDown(key) => (key, true),
+            Message::KeyUp(key) => (key, false),
+            _ => return,
+        };
+        if let Some(k) = self.0.get(key.0 as usize) {
+            k.store(held, Ordering::Relaxed);
+        }
+    }
+    pub fn is_held(&self, key: Key) -> bool {
+        self.0
+            .get(key.0 as usize)
+            .map(|k| k.load(Ordering::Relaxed))
+            .unwrap_or(false)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = Key> + '_ {
+        (0..KEY_COUNT as u8).map(Key).filter(|key| self.is_held(*key))
+    }
+}
+
+impl Default for HeldKeys {
+    fn default() -> Self {
+        HeldKeys::new()
+    }
+}
+
+// bookkeeping for checking the Launchpad's echoes against what we sent it. only simple colours
+// (i.e. notes) are echoed, so those are all that get checked.
+//
+// there's no way to match up an echo with the command that caused it other than by key, so each
+// key just remembers the last colour sent to it. if a key is sent two colours in quick succession,
+// the echo of the first will look like a mismatch, and the key will be sent its (correct) colour
+// again unnecessarily; that's harmless. a key that never echoes at all (because the note got lost,
+// or the Launchpad was asleep) is sent its colour again once `ECHO_TIMEOUT` has passed.
+#[derive(Debug, Default)]
+struct Echoes {
+    enabled: bool,
+    // what we're waiting to hear back, and when we sent it
+    pending: HashMap<Key, (SimpleColor, Instant)>,
+    // keys that need sending again, because what came back wasn't what we expected
+    mismatched: Vec<Key>,
+}
+
+// how long an echo has to turn up before we assume the note never arrived
+const ECHO_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl Echoes {
+    fn sent(&mut self, key: Key, color: SimpleColor) {
+        if self.enabled {
+            self.pending.insert(key, (color, Instant::now()));
+        }
+    }
+    // an echo of 0 or 127 is indistinguishable from a key being released or pressed, so when
+    // we're waiting for one of those, assume that's what it is
+    fn reinterpret(&mut self, message: Message) -> Message {
+        if !self.enabled {
+            return message;
+        }
+        let expected = |key| self.pending.get(&key).map(|(color, _)| *color);
+        let message = match message {
+            Message::KeyDown(key) if expected(key) == Some(SimpleColor::Static(127)) => {
+                Message::LedEcho(key, SimpleColor::Static(127))
+            }
+            Message::KeyUp(key) if expected(key) == Some(SimpleColor::Static(0)) => {
+                Message::LedEcho(key, SimpleColor::Static(0))
+            }
+            message => message,
+        };
+        if let Message::LedEcho(key, color) = message {
+            if expected(key) != Some(color) {
+                self.mismatched.push(key);
+            }
+            self.pending.remove(&key);
+        }
+        message
+    }
+    // the keys that should be sent again
+    fn stale(&mut self) -> Vec<Key> {
+        let mut stale = std::mem::take(&mut self.mismatched);
+        self.pending.retain(|key, (_, sent)| {
+            if sent.elapsed() > ECHO_TIMEOUT {
+                stale.push(*key);
+                false
+            } else {
+                true
+            }
+        });
+        stale
+    }
+}
+
+// worn-out buttons can bounce, registering several presses for one. this drops any press that
+// comes too soon after the last one of the same key, going by the timestamps MIDI gives us (in
+// microseconds).
+#[derive(Debug)]
+struct Debounce {
+    window: u64,
+    last_press: [Option<u64>; KEY_COUNT],
+}
+
+impl Debounce {
+    // whether `message` should be let through
+    fn accept(&mut self, ts: u64, message: &Message) -> bool {
+        let key = match *message {
+            Message::KeyDown(key) if self.window > 0 => key,
+            _ => return true,
+        };
+        let last_press = match self.last_press.get_mut(key.0 as usize) {
+            Some(last_press) => last_press,
+            None => return true,
+        };
+        if matches!(*last_press, Some(last) if ts.saturating_sub(last) < self.window) {
+            return false;
+        }
+        *last_press = Some(ts);
+        true
+    }
+}
+
+// some MIDI backends hand over long SysEx messages a piece at a time, so this glues the pieces
+// back together before anyone tries to make sense of them
+#[derive(Debug, Default)]
+struct SysexBuffer(Vec<u8>);
+
+// much longer than anything the Launchpad sends, so anything bigger is garbage
+const MAX_SYSEX_LEN: usize = 256;
+
+impl SysexBuffer {
+    // returns a whole message, if there is one yet
+    fn push<'a>(&'a mut self, data: &'a [u8]) -> Option<&'a [u8]> {
+        // the last message was finished, so start afresh
+        if self.0.last() == Some(&0xf7) {
+            self.0.clear();
+        }
+        match *data.first()? {
+            0xf0 => self.0.clear(),
+            // realtime messages can turn up in the middle of a SysEx without interrupting it
+            0xf8.. => return Some(data),
+            // any other status byte means that we're not in a SysEx (any more, if we were)
+            status if status >= 0x80 && status != 0xf7 => {
+                self.0.clear();
+                return Some(data);
+            }
+            // a stray continuation, with nothing to continue
+            _ if self.0.is_empty() => return None,
+            _ => {}
+        }
+        // the common case: it all arrived at once
+        if self.0.is_empty() && data.last() == Some(&0xf7) {
+            return Some(data);
+        }
+        if self.0.len() + data.len() > MAX_SYSEX_LEN {
+            trace!("dropping oversized SysEx");
+            self.0.clear();
+            return None;
+        }
+        self.0.extend_from_slice(data);
+        if self.0.last() == Some(&0xf7) {
+            Some(&self.0)
+        } else {
+            None
+        }
+    }
+}
+
+// move the keys in a message from where they are on the device to where everyone else thinks
+// they are. `None` means the message was from a key that isn't anywhere, and should be ignored.
+fn orient_message(orientation: Orientation, message: Message) -> Option<Message> {
+    Some(match message {
+        Message::KeyDown(key) => Message::KeyDown(orientation.to_logical(key)?),
+        Message::KeyUp(key) => Message::KeyUp(orientation.to_logical(key)?),
+        Message::LedEcho(key, color) => Message::LedEcho(orientation.to_logical(key)?, color),
+        message => message,
+    })
+}
+
+// what a `full_update` had to do to bring the Launchpad up to date. each simple colour gets its own
+// note message, but all the complex ones go in (at most) one SysEx message.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct UpdateReport {
+    pub cells_changed: usize,
+    pub simple_messages: usize,
+    pub complex_messages: usize,
+}
+
+// how much has been sent, and how long it took, for finding out how fast the connection to the
+// Launchpad really is. without the `stats` feature, none of this is kept track of at all.
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub messages: u64,
+    pub bytes: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+#[cfg(feature = "stats")]
+impl Stats {
+    fn new() -> Stats {
+        Stats::default()
+    }
+    pub fn average_latency(&self) -> Duration {
+        if self.messages == 0 {
+            Duration::ZERO
+        } else {
+            self.total_latency / self.messages.try_into().unwrap_or(u32::MAX)
+        }
+    }
+    fn time<T>(&mut self, bytes: usize, send: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = send();
+        let latency = start.elapsed();
+        self.messages += 1;
+        self.bytes += bytes as u64;
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+        result
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+#[derive(Debug)]
+struct Stats;
+
+#[cfg(not(feature = "stats"))]
+impl Stats {
+    fn new() -> Stats {
+        Stats
+    }
+    fn time<T>(&mut self, _bytes: usize, send: impl FnOnce() -> T) -> T {
+        send()
+    }
+}
+
+// replies that somebody is blocked waiting for: each incoming message is given to (and removes)
+// the oldest waiter whose predicate matches it, as well as being passed on to the callback as
+// usual. the Launchpad answers in the order it was asked, so that's the waiter it's the answer
+// to. waiters that give up are left behind until their (late) answer turns up, so that it can't
+// be mistaken for the answer to a newer question, or until the time they're kept for runs out,
+// in case the answer got lost altogether (otherwise every answer after it would be one behind).
+type Waiting = Arc<Mutex<Vec<(fn(&Message) -> bool, mpsc::Sender<Message>, Instant)>>>;
+
+// everyone else who wants every message, as well as the callback, see `Launchpad::add_listener`
+type Listeners = Arc<Mutex<Vec<Box<dyn FnMut(u64, Message) + Send>>>>;
+
+// how long to wait for the Launchpad to answer a question
+const QUERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+// older firmware silently ignores RGB colours for scrolling text. this is a guess at the first
+// version that doesn't (going by the release notes), so correct it if you find out otherwise!
+const MIN_RGB_TEXT_FIRMWARE: u32 = 407;
+
+// the version bytes are each a single decimal digit
+pub fn version_number(version: [u8; 4]) -> u32 {
+    version.iter().fold(0, |n, digit| n * 10 + *digit as u32)
+}
+
+// which Novation device this is, from its answer to a device inquiry: the family and member codes
+// are each two bytes, least significant first, put together as bytes (not 7-bit MIDI data) so
+// that they read the same in hex as in the manual
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeviceId {
+    pub family: u16,
+    pub member: u16,
+    pub version: [u8; 4],
+}
+
+// what the Mini MK3 says it is: its application and bootloader have different family codes
+pub const MINI_MK3_FAMILY: u16 = 0x0113;
+pub const MINI_MK3_BOOTLOADER_FAMILY: u16 = 0x1113;
+// and the others' applications
+pub const X_FAMILY: u16 = 0x0103;
+pub const PRO_MK3_FAMILY: u16 = 0x0123;
+
+// what this particular Launchpad can do, so that features which depend on it can all ask in the
+// same place rather than checking firmware versions themselves
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Capabilities {
+    // whether scrolling text can be given an RGB colour, rather than only a palette one
+    pub rgb_text: bool,
+    // fader layouts that can be set up from outside a DAW, which the X and the Pro have. the
+    // Mini MK3's are in its programmer's reference too, but only for DAWs to use
+    pub faders: bool,
+    // polyphonic aftertouch, which the X and the Pro have, and the Minis' pads don't at all
+    pub pressure: bool,
+    pub max_colors_per_frame: usize,
+}
+
+impl Capabilities {
+    pub fn for_device(model: Model, version: [u8; 4]) -> Capabilities {
+        let bigger = matches!(model, Model::X | Model::ProMk3);
+        Capabilities {
+            rgb_text: version_number(version) >= MIN_RGB_TEXT_FIRMWARE,
+            faders: bigger,
+            pressure: bigger,
+            max_colors_per_frame: MAX_COLORS_PER_FRAME,
+        }
+    }
+}
+
+// what the MIDI connections are called, as seen in e.g. `aconnect -l` or a JACK patchbay
+#[derive(Clone, Debug)]
+pub struct MidiNames {
+    pub input_client: String,
+    pub output_client: String,
+    pub input_port: String,
+    pub output_port: String,
+}
+
+impl Default for MidiNames {
+    fn default() -> Self {
+        MidiNames {
+            input_client: "midir input".into(),
+            output_client: "midir output".into(),
+            input_port: "from launchpad".into(),
+            output_port: "to launchpad".into(),
         }
     }
 }
 
 impl Launchpad {
     pub fn connect<T: FnMut(u64, Message) + Send + 'static>(
+        callback: T,
+    ) -> Result<Launchpad, ConnectionError> {
+        Launchpad::connect_nth(0, callback)
+    }
+
+    // connect to the `index`th Launchpad plugged in (in whatever order the MIDI backend lists
+    // them), for driving more than one at once. nothing is shared between `Launchpad`s, so each
+    // can be used independently of the others.
+    pub fn connect_nth<T: FnMut(u64, Message) + Send + 'static>(
+        index: usize,
+        callback: T,
+    ) -> Result<Launchpad, ConnectionError> {
+        // the client names show up in e.g. `aconnect -l`, so it's nice if they're distinguishable
+        let defaults = MidiNames::default();
+        let names = MidiNames {
+            input_client: format!("{} {}", defaults.input_client, index),
+            output_client: format!("{} {}", defaults.output_client, index),
+            ..defaults
+        };
+        Launchpad::connect_with_names(index, &names, callback)
+    }
+
+    // like `connect_nth`, but with the MIDI connections named as given, e.g. to tell several
+    // instances of the same program apart
+    pub fn connect_with_names<T: FnMut(u64, Message) + Send + 'static>(
+        index: usize,
+        names: &MidiNames,
         mut callback: T,
     ) -> Result<Launchpad, ConnectionError> {
-        let midi_in = midir::MidiInput::new("midir input")?;
-        let midi_out = midir::MidiOutput::new("midir output")?;
-
-        let midi_out_port = midi_out
-            .ports()
-            .into_iter()
-            // .find(|p| midi_out.port_name(p).unwrap().contains("LPMiniMK3 MI"))
-            .find(|p| midi_out.port_name(p).unwrap().contains("LPMiniMK3 DA"))
-            .ok_or(ConnectionError::NotFoundError)?;
-        let out_con = midi_out.connect(&midi_out_port, "to launchpad")?;
-
-        let midi_in_port = midi_in
-            .ports()
-            .into_iter()
-            // .find(|p| midi_in.port_name(p).unwrap().contains("LPMiniMK3 MI"))
-            .find(|p| midi_in.port_name(p).unwrap().contains("LPMiniMK3 DA"))
-            .expect("no launchpad found");
+        let midi_in = midir::MidiInput::new(&names.input_client)?;
+        let midi_out = midir::MidiOutput::new(&names.output_client)?;
+
+        let midi_out_port = find_port(midi_out.ports(), |p| midi_out.port_name(p).ok(), index)?;
+        debug!(
+            "connecting to output port {:?}",
+            midi_out.port_name(&midi_out_port)
+        );
+        let out_con = midi_out.connect(&midi_out_port, &names.output_port)?;
+
+        let midi_in_port = find_port(midi_in.ports(), |p| midi_in.port_name(p).ok(), index)?;
+        debug!(
+            "connecting to input port {:?}",
+            midi_in.port_name(&midi_in_port)
+        );
+        let waiting: Waiting = Arc::new(Mutex::new(Vec::new()));
+        let listeners: Listeners = Arc::new(Mutex::new(Vec::new()));
+        let held = Arc::new(HeldKeys::new());
+        let echoes = Arc::new(Mutex::new(Echoes::default()));
+        let orientation = Arc::new(Mutex::new(Orientation::Normal));
+        // until it says otherwise (below), assume it's one of the ones `find_port` looks for
+        let model = Arc::new(Mutex::new(Model::MiniMk3));
+        let debounce = Arc::new(Mutex::new(Debounce {
+            window: 0,
+            last_press: [None; KEY_COUNT],
+        }));
         let in_con = midi_in.connect(
             &midi_in_port,
-            "from launchpad",
-            move |ts, data, _| callback(ts, data.into()),
+            &names.input_port,
+            {
+                let waiting = Arc::clone(&waiting);
+                let listeners = Arc::clone(&listeners);
+                let held = Arc::clone(&held);
+                let echoes = Arc::clone(&echoes);
+                let debounce = Arc::clone(&debounce);
+                let orientation = Arc::clone(&orientation);
+                let model = Arc::clone(&model);
+                let mut sysex = SysexBuffer::default();
+                move |ts, data, _| {
+                    trace!("received {:02x?} at {}", data, ts);
+                    let data = match sysex.push(data) {
+                        Some(data) => data,
+                        None => return,
+                    };
+                    let message = Message::parse(*model.lock(), data);
+                    let message = match orient_message(*orientation.lock(), message) {
+                        Some(message) => message,
+                        None => return,
+                    };
+                    let message = echoes.lock().reinterpret(message);
+                    trace!("received message {:?}", message);
+                    if !debounce.lock().accept(ts, &message) {
+                        trace!("ignoring bounce");
+                        return;
+                    }
+                    held.update(&message);
+                    let mut waiting = waiting.lock();
+                    let now = Instant::now();
+                    waiting.retain(|(_, _, expires)| *expires > now);
+                    if let Some(i) = waiting.iter().position(|(matches, ..)| matches(&message)) {
+                        let (_, tx, _) = waiting.remove(i);
+                        let _ = tx.send(message.clone());
+                    }
+                    drop(waiting);
+                    listeners.lock().retain_mut(|listener| {
+                        let message = message.clone();
+                        let ok = panic::catch_unwind(AssertUnwindSafe(|| listener(ts, message)));
+                        if ok.is_err() {
+                            debug!("dropping a listener that panicked");
+                        }
+                        ok.is_ok()
+                    });
+                    callback(ts, message)
+                }
+            },
             (),
         )?;
         let mut launchpad = Launchpad {
             out_con,
             _in_con: in_con,
+            waiting,
+            listeners,
+            held,
+            echoes,
+            debounce,
+            orientation,
+            model,
+            stats: Stats::new(),
             send_buf: Vec::with_capacity(10),
-            complex_color_buf: Vec::with_capacity(81),
+            max_message_size: None,
+            firmware_version: None,
+            capabilities: None,
+            restore_mode_on_drop: true,
+            complex_color_buf: Vec::with_capacity(MAX_COLORS_PER_FRAME),
             // current: [Color::Simple(SimpleColor::Static(0)); 100],
-            current: HashMap::with_capacity(81),
+            current: HashMap::with_capacity(MAX_COLORS_PER_FRAME),
+            frame: None,
+            state: StateWatch {
+                state: ConnectionState::Connected,
+                observer: None,
+            },
         };
-        for key in rect(11, 99) {
-            launchpad.current.insert(key, Color::Simple(SimpleColor::Static(0)));
+        for key in grid_full() {
+            launchpad.current.insert(key, Color::OFF);
         }
         // switch to programmer mode
         launchpad.send(&Command::SetProgrammerMode(true))?;
+        // find out what it really is, and so what it can do. if it doesn't answer, it's left as a
+        // Mini MK3 whose capabilities aren't known
+        if let Ok(id) = launchpad.identify() {
+            if let Some(model) = Model::from_family(id.family) {
+                *launchpad.model.lock() = model;
+                launchpad.firmware_version = Some(id.version);
+                launchpad.capabilities = Some(Capabilities::for_device(model, id.version));
+            }
+        }
         Ok(launchpad)
     }
 
+    // like `connect`, but messages come out of a tokio channel instead of going to a callback. if
+    // nobody keeps up with the receiver, the MIDI input thread (and so any replies that a query is
+    // waiting for) stalls until they do.
+    #[cfg(feature = "tokio")]
+    pub fn connect_async(
+    ) -> Result<(Launchpad, tokio::sync::mpsc::Receiver<(u64, Message)>), ConnectionError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        let launchpad = Launchpad::connect(move |ts, message| {
+            // if the receiver's been dropped, nobody's listening anyway
+            let _ = tx.blocking_send((ts, message));
+        })?;
+        Ok((launchpad, rx))
+    }
+
+    // like `send` and `full_update`, but moved off the executor while they (potentially) block.
+    // this uses `block_in_place`, so it only works on the multi-threaded runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn send_async(&mut self, command: &Command<'_>) -> Result<(), ConnectionError> {
+        tokio::task::block_in_place(|| self.send(command))
+    }
+    #[cfg(feature = "tokio")]
+    pub async fn full_update_async(
+        &mut self,
+        new: &HashMap<Key, Color>,
+    ) -> Result<UpdateReport, ConnectionError> {
+        tokio::task::block_in_place(|| self.full_update(new))
+    }
+
+    // have `listener` called with every message too, alongside the callback from `connect`. listeners
+    // are called in the order they were added, all before the callback. a listener that panics is
+    // dropped (once the panic hook has said so, as usual), and the others still get the message;
+    // the callback isn't guarded like this, so a panic there is just as fatal as it always was.
+    // listeners are called on the MIDI input thread, so a slow one holds up everything after it.
+    pub fn add_listener<T: FnMut(u64, Message) + Send + 'static>(&mut self, listener: T) {
+        self.listeners.lock().push(Box::new(listener));
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        self.state.state
+    }
+
+    // have `observer` called with the old state and the new one whenever the `ConnectionState`
+    // changes. there's only ever one observer: setting another replaces it. it's called on
+    // whichever thread caused the change (so far, that's always the one using the `Launchpad`),
+    // including from inside `Drop`. it isn't called for the state at the time it's set, so look at
+    // `connection_state` for that.
+    pub fn on_state_change<T: FnMut(ConnectionState, ConnectionState) + Send + 'static>(
+        &mut self,
+        observer: T,
+    ) {
+        self.state.observer = Some(Box::new(observer));
+    }
+
+    // disconnect now, rather than whenever the `Launchpad` happens to be dropped. this is exactly
+    // what dropping it does, it's just easier to spot.
+    pub fn close(self) {}
+
+    // some MIDI backends silently truncate long SysEx messages; setting a limit here makes
+    // oversized messages fail with `MessageTooLarge` instead
+    pub fn set_max_message_size(&mut self, max_message_size: Option<usize>) {
+        self.max_message_size = max_message_size;
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    // whether to switch the Launchpad out of programmer mode when it's dropped. turning this off
+    // leaves it just as it is, e.g. for another process to pick up where this one left off.
+    pub fn set_restore_mode_on_drop(&mut self, restore: bool) {
+        self.restore_mode_on_drop = restore;
+    }
+
+    // translate keys so that everything's the right way up when the Launchpad is mounted
+    // sideways (or upside down, or in a mirror)
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        *self.orientation.lock() = orientation;
+    }
+
+    // ignore presses of a key that come within `debounce` of the last one, for buttons that
+    // bounce. zero (the default) turns this off.
+    pub fn set_debounce(&mut self, debounce: Duration) {
+        self.debounce.lock().window = debounce.as_micros() as u64;
+    }
+
     fn _send(
         command: &Command,
         send_buf: &mut Vec<u8>,
         out_con: &mut midir::MidiOutputConnection,
+        max_message_size: Option<usize>,
+        orientation: Orientation,
+        stats: &mut Stats,
+        state: &mut StateWatch,
     ) -> Result<(), ConnectionError> {
         send_buf.clear();
-        command.append_to_vec(send_buf).unwrap();
-        out_con.send(send_buf)?;
+        match *command {
+            _ if orientation == Orientation::Normal => command.append_to_vec(send_buf),
+            Command::KeyOn(key, color) => match orientation.to_physical(key) {
+                Some(key) => Command::KeyOn(key, color).append_to_vec(send_buf),
+                None => return Ok(()),
+            },
+            Command::KeyOff(key) => match orientation.to_physical(key) {
+                Some(key) => Command::KeyOff(key).append_to_vec(send_buf),
+                None => return Ok(()),
+            },
+            Command::SetColors(colors) => {
+                let colors: Vec<_> = colors
+                    .iter()
+                    .filter_map(|(key, color)| Some((orientation.to_physical(*key)?, *color)))
+                    .collect();
+                Command::SetColors(&colors).append_to_vec(send_buf)
+            }
+            _ => command.append_to_vec(send_buf),
+        }
+        .unwrap();
+        if let Some(max) = max_message_size {
+            if send_buf.len() > max {
+                return Err(ConnectionError::MessageTooLarge(send_buf.len()));
+            }
+        }
+        trace!("sending {:02x?}", send_buf);
+        let result = stats.time(send_buf.len(), || out_con.send(send_buf));
+        state.sent(out_con, result)?;
         Ok(())
     }
 
     pub fn send(&mut self, command: &Command) -> Result<(), ConnectionError> {
-        Launchpad::_send(command, &mut self.send_buf, &mut self.out_con)?;
+        if let Command::ScrollText {
+            loops,
+            speed,
+            color: Some(TextColor::Rgb(r, g, b)),
+            text,
+        } = *command
+        {
+            // only what was found out when connecting is used, since asking now would hold up the
+            // send. if it didn't say what firmware it's running then, just hope for the best
+            if matches!(self.capabilities, Some(c) if !c.rgb_text) {
+                warn!("this Launchpad's firmware is too old for RGB text, using the nearest palette colour instead");
+                let color = palette::nearest(r << 1, g << 1, b << 1);
+                return self.send(&Command::ScrollText {
+                    loops,
+                    speed,
+                    color: Some(TextColor::Palette(color)),
+                    text,
+                });
+            }
+        }
+        Launchpad::_send(
+            command,
+            &mut self.send_buf,
+            &mut self.out_con,
+            self.max_message_size,
+            *self.orientation.lock(),
+            &mut self.stats,
+            &mut self.state,
+        )?;
         if let Command::KeyOn(key, color) = command {
             // self.current[*key as usize] = Color::Simple(*color);
             *self.current.get_mut(&key).unwrap() = Color::Simple(*color);
+            self.echoes.lock().sent(*key, *color);
+        } else if let Command::KeyOff(key) = command {
+            // it goes out as a `KeyOn` of colour 0, so that's what'll echo
+            *self.current.get_mut(key).unwrap() = Color::OFF;
+            self.echoes.lock().sent(*key, SimpleColor::Static(0));
         } else if let Command::SetColors(colors) = command {
+            let mut echoes = self.echoes.lock();
             for (key, color) in colors.iter() {
                 // self.current[*key as usize] = Color::Complex(*color);
                 *self.current.get_mut(&key).unwrap() = Color::Complex(*color);
+                echoes.pending.remove(key);
             }
         }
         Ok(())
     }
 
-    pub fn full_update(&mut self, new: &HashMap<Key, Color>) -> Result<(), ConnectionError> {
+    // send a message that's already been encoded (by `Command::to_bytes`), skipping all the work of
+    // building it (including `set_orientation`'s: the keys in it are sent exactly as they are).
+    // there's no way to work out from the bytes what the LEDs are now showing, so `colors` has to
+    // say: it's up to the caller to keep it in step with `bytes`, otherwise `full_update` will skip
+    // keys it thinks are already right.
+    pub fn send_prerendered(
+        &mut self,
+        bytes: &[u8],
+        colors: &[(Key, Color)],
+    ) -> Result<(), ConnectionError> {
+        if let Some(max) = self.max_message_size {
+            if bytes.len() > max {
+                return Err(ConnectionError::MessageTooLarge(bytes.len()));
+            }
+        }
+        trace!("sending {:02x?}", bytes);
+        let out_con = &mut self.out_con;
+        let result = self.stats.time(bytes.len(), || out_con.send(bytes));
+        self.state.sent(out_con, result)?;
+        for (key, color) in colors {
+            *self.current.get_mut(key).unwrap() = *color;
+        }
+        Ok(())
+    }
+
+    // send any MIDI message at all, for trying out things there isn't a `Command` for yet. it's
+    // only checked for being one complete message (a SysEx from 0xf0 to 0xf7, or a channel
+    // message of the right length): whether the Launchpad makes sense of it is up to you. nothing
+    // is noted down in `current`, so if it changes any LEDs, `full_update` won't know.
+    pub fn send_raw(&mut self, bytes: &[u8]) -> Result<(), ConnectionError> {
+        let complete = match bytes {
+            [0xf0, body @ .., 0xf7] => body.iter().all(|b| *b < 0x80),
+            [0x80..=0xbf | 0xe0..=0xef, a, b] => *a < 0x80 && *b < 0x80,
+            [0xc0..=0xdf, a] => *a < 0x80,
+            _ => false,
+        };
+        if !complete {
+            return Err(ConnectionError::MalformedMessage(bytes.to_vec()));
+        }
+        self.send_prerendered(bytes, &[])
+    }
+
+    pub fn full_update(
+        &mut self,
+        new: &HashMap<Key, Color>,
+    ) -> Result<UpdateReport, ConnectionError> {
+        self.update_with(|key| new[&key])
+    }
+
+    // like `full_update`, but asks `f` for the colour of each key rather than needing a whole map
+    pub fn update_with(
+        &mut self,
+        f: impl Fn(Key) -> Color,
+    ) -> Result<UpdateReport, ConnectionError> {
+        self.update(f, false)
+    }
+
+    // the other way to draw: `begin_frame` hands out a copy of what's showing to draw over, and
+    // nothing reaches the Launchpad until `commit_frame`. beginning a frame while there's already
+    // one in progress throws the old one away.
+    pub fn begin_frame(&mut self) -> &mut Framebuffer {
+        self.frame.insert(Framebuffer {
+            cells: self.current.clone(),
+        })
+    }
+
+    // send the frame from `begin_frame` (if there is one) in a single burst. unlike `full_update`,
+    // static simple colours go in the SysEx too, since they look just the same as their complex
+    // equivalents, so the whole frame changes at once. flashing and pulsing ones still need a note
+    // message each (see `Color::batch_equivalent`).
+    pub fn commit_frame(&mut self) -> Result<UpdateReport, ConnectionError> {
+        match self.frame.take() {
+            Some(frame) => self.update(|key| frame[key], true),
+            None => Ok(UpdateReport::default()),
+        }
+    }
+
+    // send the whole of `fb`, without comparing it against `current` first. when nearly every cell
+    // changes every frame (like a full-screen animation), the comparison is wasted work, and this
+    // is quicker. simple colours are batched up as in `commit_frame`, so it's still one SysEx plus
+    // a note message for each flashing or pulsing cell.
+    pub fn force_frame(&mut self, fb: &Framebuffer) -> Result<UpdateReport, ConnectionError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("force_frame").entered();
         self.complex_color_buf.clear();
-        for key in rect(11, 99) {
-            if new[&key] != self.current[&key] {
-                *self.current.get_mut(&key).unwrap() = new[&key];
-                match new[&key] {
-                    Color::Simple(c) => Launchpad::_send(
-                        &Command::KeyOn(key as u8, c),
+        let mut echoes = self.echoes.lock();
+        let mut report = UpdateReport::default();
+        for key in grid_full() {
+            report.cells_changed += 1;
+            match (fb[key], fb[key].batch_equivalent()) {
+                (_, Some(c)) => self.complex_color_buf.push((key, c)),
+                (Color::Simple(c), None) => {
+                    Launchpad::_send(
+                        &Command::KeyOn(key, c),
                         &mut self.send_buf,
                         &mut self.out_con,
-                    )?,
-                    Color::Complex(c) => self.complex_color_buf.push((key as u8, c)),
+                        self.max_message_size,
+                        *self.orientation.lock(),
+                        &mut self.stats,
+                        &mut self.state,
+                    )?;
+                    report.simple_messages += 1;
+                    echoes.sent(key, c);
                 }
+                (Color::Complex(c), None) => self.complex_color_buf.push((key, c)),
             }
         }
-        if !self.complex_color_buf.is_empty() {
+        drop(echoes);
+        report.complex_messages = self.flush_complex_colors()?;
+        self.current.clone_from(&fb.cells);
+        Ok(report)
+    }
+
+    // `batch` is whether to turn simple colours into complex ones where they look the same, see
+    // `commit_frame`
+    fn update(
+        &mut self,
+        f: impl Fn(Key) -> Color,
+        batch: bool,
+    ) -> Result<UpdateReport, ConnectionError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("update").entered();
+        self.complex_color_buf.clear();
+        let mut echoes = self.echoes.lock();
+        // anything the Launchpad disagreed with (or didn't answer about) gets sent again. this is
+        // empty unless verification is on.
+        let stale = echoes.stale();
+        let mut report = UpdateReport::default();
+        for key in grid_full() {
+            let color = f(key);
+            if color != self.current[&key] || stale.contains(&key) {
+                *self.current.get_mut(&key).unwrap() = color;
+                report.cells_changed += 1;
+                let batched = if batch { color.batch_equivalent() } else { None };
+                match (color, batched) {
+                    (_, Some(c)) => self.complex_color_buf.push((key, c)),
+                    (Color::Simple(c), None) => {
+                        Launchpad::_send(
+                            &Command::KeyOn(key, c),
+                            &mut self.send_buf,
+                            &mut self.out_con,
+                            self.max_message_size,
+                            *self.orientation.lock(),
+                            &mut self.stats,
+                            &mut self.state,
+                        )?;
+                        report.simple_messages += 1;
+                        echoes.sent(key, c);
+                    }
+                    (Color::Complex(c), None) => self.complex_color_buf.push((key, c)),
+                }
+            }
+        }
+        drop(echoes);
+        report.complex_messages = self.flush_complex_colors()?;
+        if batch {
+            // flushing noted the batched ones down as complex, so put them back how they were asked
+            // for, or they'd all look changed next time
+            for key in grid_full() {
+                *self.current.get_mut(&key).unwrap() = f(key);
+            }
+        }
+        Ok(report)
+    }
+
+    // send everything in `complex_color_buf` (in as many messages as it takes), note it down in
+    // `current`, and empty it again, returning how many messages that was. everything that sends
+    // complex colours in bulk builds them up in there first, so they all share the one allocation.
+    fn flush_complex_colors(&mut self) -> Result<usize, ConnectionError> {
+        let mut messages = 0;
+        for chunk in self.complex_color_buf.chunks(MAX_COLORS_PER_FRAME) {
             Launchpad::_send(
-                &Command::SetColors(&self.complex_color_buf),
+                &Command::SetColors(chunk),
                 &mut self.send_buf,
                 &mut self.out_con,
+                self.max_message_size,
+                *self.orientation.lock(),
+                &mut self.stats,
+                &mut self.state,
             )?;
+            messages += 1;
+        }
+        let mut echoes = self.echoes.lock();
+        for (key, color) in self.complex_color_buf.drain(..) {
+            *self.current.get_mut(&key).unwrap() = Color::Complex(color);
+            echoes.pending.remove(&key);
+        }
+        Ok(messages)
+    }
+
+    // hand anything still waiting to go out to midir, so that nothing sent afterwards (like a query)
+    // overtakes it. midir doesn't hold on to anything itself (each `send` goes straight to the
+    // backend, which delivers in order), so all that can be left is complex colours in
+    // `complex_color_buf`, from a send that failed halfway. a frame from `begin_frame` doesn't
+    // count, since it isn't meant to be sent until `commit_frame`.
+    pub fn flush(&mut self) -> Result<(), ConnectionError> {
+        self.flush_complex_colors()?;
+        Ok(())
+    }
+
+    // set every LED (the grid, the buttons around it, and the logo) to `color` in one go. there's no
+    // message for filling the whole thing, so this is a single SysEx listing all 81 of them, which
+    // is still quicker than a `full_update` of lots of different-looking cells.
+    pub fn fill(&mut self, color: ComplexColor) -> Result<(), ConnectionError> {
+        self.complex_color_buf.clear();
+        self.complex_color_buf.extend(grid_full().map(|key| (key, color)));
+        self.flush_complex_colors()?;
+        Ok(())
+    }
+
+    // turn every LED off in one go (like `fill`), and note them all down in `current` as `Color::OFF`,
+    // so the next `full_update` puts back everything that's meant to be lit
+    pub fn clear_all(&mut self) -> Result<(), ConnectionError> {
+        self.fill(ComplexColor::Static(0))?;
+        for color in self.current.values_mut() {
+            *color = Color::OFF;
+        }
+        Ok(())
+    }
+
+    // like `Framebuffer::from_grid` (so `grid[0][0]` is the top left), but straight onto the
+    // Launchpad: the buttons around the edge are left as they are, and only the cells that have
+    // changed are sent
+    pub fn show_grid(&mut self, grid: &Grid) -> Result<UpdateReport, ConnectionError> {
+        let mut new = self.current.clone();
+        new.extend(grid_cells(grid));
+        self.full_update(&new)
+    }
+
+    // show an image on the 8x8 grid. it's cropped to a square around its centre (so it isn't
+    // squashed), then scaled down with a triangle (i.e. bilinear) filter, which averages out
+    // detail that a nearest-neighbour scale would pick pixels out of at random.
+    #[cfg(feature = "image")]
+    pub fn display_image(&mut self, path: &str) -> Result<(), ConnectionError> {
+        use image::GenericImageView;
+        let image = image::open(path)?;
+        let (width, height) = image.dimensions();
+        let side = width.min(height);
+        let image = image
+            .crop_imm((width - side) / 2, (height - side) / 2, side, side)
+            .resize_exact(8, 8, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+        self.complex_color_buf.clear();
+        self.complex_color_buf.extend(image.enumerate_pixels().map(|(x, y, pixel)| {
+            let [r, g, b] = pixel.0.map(|c| gamma_correct(c, DEFAULT_GAMMA));
+            (coords_to_key_topleft(x as u8, y as u8), ComplexColor::Rgb(r, g, b))
+        }));
+        self.flush_complex_colors()?;
+        Ok(())
+    }
+
+    // have the Launchpad echo back every note it's sent, and check each update against what it
+    // says its LEDs are showing, sending again anything that's drifted (e.g. after it's been
+    // asleep, or the USB connection has hiccuped). this doubles the MIDI traffic, so it's off by
+    // default.
+    pub fn set_led_verification(&mut self, enabled: bool) -> Result<(), ConnectionError> {
+        // the "external" flag is the one that echoes what we send
+        self.send(&Command::SetLedFeedback(LedFeedback {
+            internal: false,
+            external: enabled,
+        }))?;
+        let mut echoes = self.echoes.lock();
+        echoes.enabled = enabled;
+        echoes.pending.clear();
+        echoes.mismatched.clear();
+        Ok(())
+    }
+
+    // the Mini MK3's programmer's reference doesn't have a message for this: the live/programmer
+    // mode switch (which `connect` uses) is forgotten as soon as the Launchpad loses power, and it
+    // always comes back up in live mode. so there's no `Command` for it, and this just says so
+    // rather than pretending it worked.
+    pub fn set_boot_mode(&mut self, _mode: BootMode) -> Result<(), ConnectionError> {
+        Err(ConnectionError::Unsupported("choosing which mode to boot into"))
+    }
+
+    // none of the programmer's references (the Mini MK3's, the X's, or the Pro MK3's) have a
+    // message for setting the tempo, or for making the Launchpad send clock itself: they only ever
+    // follow the clock they're sent. so there's no `Command` for this either, and the way to lock
+    // flashing and pulsing to a tempo is to send `Command::TimingClock` 24 times a beat (or to
+    // route a DAW's clock output to the Launchpad).
+    pub fn set_tempo(&mut self, _bpm: f32) -> Result<(), ConnectionError> {
+        Err(ConnectionError::Unsupported("setting the tempo"))
+    }
+
+    pub fn is_held(&self, key: Key) -> bool {
+        self.held.is_held(key)
+    }
+
+    pub fn held_keys(&self) -> impl Iterator<Item = Key> + '_ {
+        self.held.iter()
+    }
+
+    // all the keys currently held down, in ascending order
+    pub fn chord(&self) -> Vec<Key> {
+        self.held_keys().collect()
+    }
+
+    // send `command`, then wait for the first reply that `matches` accepts
+    fn request(
+        &mut self,
+        command: &Command,
+        matches: fn(&Message) -> bool,
+        timeout: Duration,
+    ) -> Result<Message, ConnectionError> {
+        // anything sent before the query gets there first (see `flush`), and the answer to an
+        // earlier query that gave up can't be taken for this one's (see `Waiting`)
+        self.flush()?;
+        let (tx, rx) = mpsc::channel();
+        // register before sending, so that the reply can't sneak past us. if it's late, it's still
+        // waited for (and thrown away) for as long again, see `Waiting`
+        let expires = Instant::now() + 2 * timeout;
+        self.waiting.lock().push((matches, tx, expires));
+        self.send(command)?;
+        rx.recv_timeout(timeout).map_err(|_| ConnectionError::Timeout)
+    }
+
+    // the application (not bootloader) version, which is only asked for once
+    pub fn firmware_version(&mut self) -> Result<[u8; 4], ConnectionError> {
+        if let Some(version) = self.firmware_version {
+            return Ok(version);
         }
+        match self.request(
+            &Command::GetVersions,
+            |message| matches!(message, Message::ApplicationVersion(_)),
+            QUERY_TIMEOUT,
+        )? {
+            Message::ApplicationVersion(version) => {
+                self.firmware_version = Some(version);
+                Ok(version)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // which model this is, as found out when connecting
+    pub fn model(&self) -> Model {
+        *self.model.lock()
+    }
+
+    // ask the Launchpad what it is
+    pub fn identify(&mut self) -> Result<DeviceId, ConnectionError> {
+        let reply = self.request(
+            &Command::GetVersions,
+            |message| {
+                matches!(
+                    message,
+                    Message::ApplicationVersion(_)
+                        | Message::BootloaderVersion(_)
+                        | Message::DeviceId { .. }
+                )
+            },
+            QUERY_TIMEOUT,
+        )?;
+        Ok(match reply {
+            Message::ApplicationVersion(version) => DeviceId {
+                family: MINI_MK3_FAMILY,
+                member: 0,
+                version,
+            },
+            Message::BootloaderVersion(version) => DeviceId {
+                family: MINI_MK3_BOOTLOADER_FAMILY,
+                member: 0,
+                version,
+            },
+            Message::DeviceId {
+                family,
+                member,
+                version,
+            } => DeviceId {
+                family,
+                member,
+                version,
+            },
+            _ => unreachable!(),
+        })
+    }
+
+    // worked out from the model and firmware version when connecting, or `None` if the Launchpad
+    // didn't say what it was then
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+
+    pub fn query_brightness(&mut self) -> Result<u8, ConnectionError> {
+        match self.request(
+            &Command::GetBrightness,
+            |message| matches!(message, Message::Brightness(_)),
+            QUERY_TIMEOUT,
+        )? {
+            Message::Brightness(brightness) => Ok(brightness),
+            _ => unreachable!(),
+        }
+    }
+
+    // scroll some text across the grid, returning once it has finished
+    pub fn scroll_text_blocking(
+        &mut self,
+        loops: bool,
+        speed: u8,
+        color: TextColor,
+        text: &str,
+        timeout: Duration,
+    ) -> Result<(), ConnectionError> {
+        if loops {
+            return Err(ConnectionError::EndlessScroll);
+        }
+        self.request(
+            &Command::ScrollText {
+                loops: Some(false),
+                speed: Some(speed),
+                color: Some(color),
+                text: Some(text),
+            },
+            |message| matches!(message, Message::TextScrollFinished),
+            timeout,
+        )?;
+        Ok(())
+    }
+
+    pub fn save_scene(&self) -> Scene {
+        Scene {
+            cells: self.current.clone(),
+        }
+    }
+
+    // only the cells that differ from what's currently displayed are sent
+    pub fn load_scene(&mut self, scene: &Scene) -> Result<UpdateReport, ConnectionError> {
+        self.full_update(&scene.cells)
+    }
+
+    // resend everything in `current`, in case the device has forgotten it (e.g. after sleeping)
+    pub fn resync(&mut self) -> Result<(), ConnectionError> {
+        self.complex_color_buf.clear();
+        for key in grid_full() {
+            match self.current[&key] {
+                Color::Simple(c) => Launchpad::_send(
+                    &Command::KeyOn(key, c),
+                    &mut self.send_buf,
+                    &mut self.out_con,
+                    self.max_message_size,
+                    *self.orientation.lock(),
+                    &mut self.stats,
+                    &mut self.state,
+                )?,
+                Color::Complex(c) => self.complex_color_buf.push((key, c)),
+            }
+        }
+        self.flush_complex_colors()?;
         Ok(())
     }
 }
 
+// something that can be drawn on like a Launchpad: either the real thing, or a stand-in for it
+pub trait LaunchpadDevice {
+    fn send(&mut self, command: &Command) -> Result<(), ConnectionError>;
+    fn full_update(&mut self, new: &Framebuffer) -> Result<UpdateReport, ConnectionError>;
+    fn resync(&mut self) -> Result<(), ConnectionError>;
+    fn chord(&self) -> Vec<Key>;
+    fn query_brightness(&mut self) -> Result<u8, ConnectionError>;
+    // every LED off, and known to be off, so whatever's drawn next is sent in full
+    fn clear_all(&mut self) -> Result<(), ConnectionError> {
+        for key in grid_full() {
+            self.send(&Command::KeyOff(key))?;
+        }
+        Ok(())
+    }
+}
+
+impl LaunchpadDevice for Launchpad {
+    fn send(&mut self, command: &Command) -> Result<(), ConnectionError> {
+        Launchpad::send(self, command)
+    }
+    // whole frames are what the UI deals in anyway, so they may as well arrive all at once
+    fn full_update(&mut self, new: &Framebuffer) -> Result<UpdateReport, ConnectionError> {
+        *self.begin_frame() = new.clone();
+        self.commit_frame()
+    }
+    fn resync(&mut self) -> Result<(), ConnectionError> {
+        Launchpad::resync(self)
+    }
+    fn chord(&self) -> Vec<Key> {
+        Launchpad::chord(self)
+    }
+    fn query_brightness(&mut self) -> Result<u8, ConnectionError> {
+        Launchpad::query_brightness(self)
+    }
+    fn clear_all(&mut self) -> Result<(), ConnectionError> {
+        Launchpad::clear_all(self)
+    }
+}
+
 impl Drop for Launchpad {
     fn drop(&mut self) {
-        if let Err(e) = self.send(&Command::SetProgrammerMode(false)) {
-            eprintln!("warning: could not deinitialise Launchpad: {}", e);
+        debug!("disconnecting");
+        // even if the last send failed, this one might not. it goes straight to the backend, since
+        // it's no use going through `Reconnecting` to put it back in programmer mode first
+        if self.restore_mode_on_drop {
+            if let Err(e) = self.out_con.send(&Command::SetProgrammerMode(false).to_bytes()) {
+                eprintln!("warning: could not deinitialise Launchpad: {}", e);
+            }
         }
+        self.state.set(ConnectionState::Disconnected);
         // we would *like* to be able to do `self.{_in,out}_con.close()` here, but since they consume
         // the connection objects and we can't consume the `Launchpad` object here, we can't.
         // ...hopefully that won't cause anything bad to happen?
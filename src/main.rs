@@ -1,13 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::panic::Location;
 use std::process;
 use std::{
     collections::hash_map::DefaultHasher,
+    env,
     hash::{Hash, Hasher},
     io::{BufRead, BufReader},
-    sync::{atomic::AtomicBool, mpsc, Arc},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
@@ -19,10 +23,14 @@ use parking_lot::Mutex;
 // use rdev::Key::*;
 
 use lp::{
-    coords_to_key, key_to_coords, rect, Color, Command, ComplexColor, Key, Launchpad, Message,
-    SimpleColor, TextColor,
+    coords_to_key, font, grid_8x8, grid_full,
+    i3::{key_to_workspace, swap_outputs_command, workspace_to_key, OutputWorkspaces, WORKSPACES},
+    key_to_coords, palette, rect, rect_normalized, tee::TeeDevice, Brightness, Color, Command, ComplexColor, Coord,
+    Framebuffer, Key, LaunchpadDevice, Message, SimpleColor, TextColor, GRID_SIZE,
 };
 
+use animations::Animation;
+
 // https://gist.github.com/sug0/b5eb2c58be74f7cda230b8c1e1994670
 fn fast_sin(mut x: f64) -> f64 {
     let (mut y, mut z) = (x, x);
@@ -50,23 +58,207 @@ fn fast_sin(mut x: f64) -> f64 {
     x
 }
 
-fn configure_signals(tx: mpsc::Sender<Event>) -> eyre::Result<()> {
-    let interrupted = Arc::new(AtomicBool::new(false));
-    for signal in signal_hook::consts::TERM_SIGNALS {
-        // if we can't shut down cleanly, just exit...
-        signal_hook::flag::register_conditional_shutdown(*signal, 1, Arc::clone(&interrupted))
-            .wrap_err("couldn't register fallback shutdown hook")?;
-        // ...but try to shut down cleanly first.
-        signal_hook::flag::register(*signal, Arc::clone(&interrupted))
-            .wrap_err("couldn't register shutdown hook")?;
+// which player playerctl should control (`None` lets playerctl pick one by itself)
+const PLAYER: Option<&str> = None;
+
+// how long to wait without any key presses before dimming the LEDs (this is only checked on the
+// periodic redraw, so it can be up to REDRAW_INTERVAL late), and how dim to make them
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const IDLE_BRIGHTNESS: u8 = Brightness::STEPS[1];
+
+// whether the press that wakes the UI up should also do whatever it would usually do, rather than
+// just waking it
+const WAKE_PRESS_ACTS: bool = false;
+
+// whether to fade out quietly on shutdown, rather than with the pinwheel
+const FADE_OUT_ON_SHUTDOWN: bool = false;
+
+// the metronome on the keyboard tab: how fast it goes, and which beats of the bar are accented
+// (so this is 4/4, with the first beat accented). it keeps its own time, since there's no MIDI
+// clock input to follow.
+const METRONOME_BPM: u32 = 100;
+const METRONOME_ACCENTS: &[bool] = &[true, false, false, false];
+// how long each beat stays lit for
+const METRONOME_FLASH: Duration = Duration::from_millis(100);
+
+// how often to redraw even if nothing has happened (to notice being idle, say), and to rerun the
+// status checks, which pick up anything that's only polled (like whether the microphone is plugged
+// in). whether anything is recording doesn't need this, since pactl tells us as soon as it changes.
+const REDRAW_INTERVAL: Duration = Duration::from_secs(10);
+
+static METRONOME_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// what to show while the LEDs are dimmed for being idle: these colours drift slowly across the
+// grid, moving on by a key every period
+const SCREENSAVER_PALETTE: &[u8] = &[41, 45, 49, 53, 49, 45];
+const SCREENSAVER_PERIOD: Duration = Duration::from_millis(500);
+
+// the notes (in Hz) to chime together when an i3 workspace goes urgent, and how long they take to
+// fade away. no notes means no sound, which is the default.
+const URGENT_CHIME: &[f32] = &[];
+const URGENT_CHIME_DURATION: Duration = Duration::from_millis(800);
+
+// what the synth sounds like to begin with (see `Timbre`); the keyboard tab can change it. anything
+// past MAX_HARMONICS is ignored, since every one of them costs a `sin` per note per sample.
+const SYNTH_TIMBRE: Timbre = Timbre::Sine;
+const MAX_HARMONICS: usize = 8;
+
+// the synth's timbres, as how loud each harmonic is (the first being the note itself). they're all
+// scaled down so the loudest a note can get stays the same.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Timbre {
+    // a plain sine, which is all the synth used to do
+    Sine,
+    // something like an organ with a few drawbars out
+    Organ,
+    // the odd harmonics, like a square wave
+    Hollow,
+}
+
+impl Timbre {
+    const ALL: [Timbre; 3] = [Timbre::Sine, Timbre::Organ, Timbre::Hollow];
+
+    fn harmonics(self) -> &'static [f32] {
+        match self {
+            Timbre::Sine => &[1.0],
+            Timbre::Organ => &[1.0, 0.5, 0.0, 0.25, 0.0, 0.0, 0.0, 0.125],
+            Timbre::Hollow => &[1.0, 0.0, 1.0 / 3.0, 0.0, 1.0 / 5.0, 0.0, 1.0 / 7.0],
+        }
+    }
+
+    // the one after this one, going round
+    fn next(self) -> Timbre {
+        let i = Timbre::ALL.iter().position(|t| *t == self).unwrap();
+        Timbre::ALL[(i + 1) % Timbre::ALL.len()]
     }
+}
+
+// how long a D-pad's buttons (like the arrows in the i3 tab) have to be held before they start
+// repeating, and how often they repeat after that
+const KEY_REPEAT_DELAY: Duration = Duration::from_millis(400);
+const KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(150);
+
+// how the clock shows the time, as a `date` format
+const CLOCK_FORMAT: &str = "%H:%M";
+
+// whether the demo (see `animations::demo`) is showing, in which case any key press stops it
+static DEMO_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// the boards (see `Ui::board`) with text scrolling across them right now, so that the clock knows
+// to wait its turn (see `Ui::scroll_text`)
+static TEXT_SCROLLING: Lazy<Mutex<HashSet<usize>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// the board everything is drawn on. a mirror (`--mirror`) shows the same board, rather than being
+// one of its own
+const MAIN_BOARD: usize = 0;
+
+// where to show that something has gone wrong (like a command failing), in what colour, and for how
+// long after the last error (see `Ui::report_error`). pressing the key puts it out early. the logo
+// isn't used for anything else, so it's free to take over.
+const ERROR_KEY: Key = Key(99);
+const ERROR_COLOR: Color = Color::flashing(5, 0);
+const ERROR_SHOWN_FOR: Duration = Duration::from_secs(60);
+
+// when the last error was reported on each board (see `Ui::board`), if it's still being shown
+static LAST_ERROR: Lazy<Mutex<HashMap<usize, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// holding all of these down together stops everything (synth notes, scrolling text, and whatever's
+// lit up) from any tab, for when something's got stuck
+const PANIC_CHORD: &[Key] = &[Key(11), Key(18), Key(81), Key(88)];
+
+// how bright (as a fraction of full brightness) a tab is when it's shown as the ghost behind
+// another (see `Ui::tab_pages`)
+const GHOST_BRIGHTNESS: f32 = 0.25;
+
+// how often to redraw while an animation (like the screensaver) is running, to move it along, or
+// while a repeat button is held down, to repeat it
+const ANIMATION_FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+// sends a redraw every `interval` while it's active, and sleeps (without waking up to check) the
+// rest of the time
+struct AnimationTicker {
+    active: Arc<AtomicBool>,
+    thread: thread::Thread,
+}
+
+impl AnimationTicker {
+    fn spawn(tx: mpsc::Sender<Event>, interval: Duration) -> eyre::Result<Self> {
+        let active = Arc::new(AtomicBool::new(false));
+        let handle = thread::Builder::new()
+            .name("lp animation ticker".into())
+            .spawn({
+                let active = Arc::clone(&active);
+                move || loop {
+                    if !active.load(Ordering::Relaxed) {
+                        // woken by `set_active` (or spuriously, which the loop takes care of)
+                        thread::park();
+                        continue;
+                    }
+                    thread::sleep(interval);
+                    // it may have stopped while we were asleep
+                    if active.load(Ordering::Relaxed) && tx.send(Event::Redraw).is_err() {
+                        break;
+                    }
+                }
+            })
+            .wrap_err("couldn't spawn animation ticker thread")?;
+        Ok(AnimationTicker {
+            active,
+            thread: handle.thread().clone(),
+        })
+    }
+
+    fn set_active(&self, active: bool) {
+        if active && !self.active.swap(true, Ordering::Relaxed) {
+            self.thread.unpark();
+        } else if !active {
+            self.active.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+fn playerctl(player: Option<&str>, args: &[&str]) -> duct::Expression {
+    let player = player.map(|p| format!("--player={}", p));
+    duct::cmd(
+        "playerctl",
+        player.iter().map(String::as_str).chain(args.iter().copied()),
+    )
+}
+
+// how long after the first shutdown signal any further ones are ignored. the shutdown animation
+// takes a little under a second, and restoring the Launchpad's mode afterwards is quick, so this is
+// enough for a clean exit even if the machine is busy. once it's over, another signal force-quits
+// straight away (leaving the Launchpad in programmer mode, since nobody's around to restore it).
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+// every signal also raises `cancel_animation`, which stops whichever blocking animation is playing:
+// the first one skips the rest of the startup animation (or an alert) so the shutdown can start,
+// and a second one skips the shutdown animation itself.
+fn configure_signals(
+    tx: mpsc::Sender<Event>,
+    cancel_animation: Arc<AtomicBool>,
+) -> eyre::Result<()> {
     let mut signals = signal_hook::iterator::Signals::new(signal_hook::consts::TERM_SIGNALS)
         .wrap_err("couldn't register interest in shutdown signals")?;
     thread::Builder::new()
         .name("lp signal handler".into())
         .spawn(move || {
-            signals.forever().next();
-            tx.send(Event::Exit).unwrap();
+            let mut first_signal = None;
+            for _ in signals.forever() {
+                cancel_animation.store(true, Ordering::Relaxed);
+                match first_signal {
+                    None => {
+                        first_signal = Some(Instant::now());
+                        tx.send(Event::Exit).unwrap();
+                    }
+                    // try to shut down cleanly first...
+                    Some(t) if t.elapsed() < SHUTDOWN_GRACE_PERIOD => {
+                        eprintln!("shutting down, signal again in a moment to force quit");
+                    }
+                    // ...but if we can't, just exit.
+                    Some(_) => process::exit(1),
+                }
+            }
         })
         .wrap_err("couldn't spawn signal handler thread")?;
     Ok(())
@@ -75,71 +267,105 @@ fn configure_signals(tx: mpsc::Sender<Event>) -> eyre::Result<()> {
 mod animations {
     use itertools::Itertools;
     use std::{
-        iter, thread,
+        iter,
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
         time::{Duration, Instant},
     };
 
-    use super::{coords_to_key, key_to_coords, Command, Key, Launchpad, SimpleColor};
+    use super::{
+        coords_to_key, grid_full, key_to_coords, Color, Command, Coord, Framebuffer, Key,
+        LaunchpadDevice, SimpleColor,
+    };
 
     const TRANS_BLUE: u8 = 37;
     const TRANS_PINK: u8 = 52;
     const TRANS_WHITE: u8 = 3;
 
-    fn up_left_from(start: u8) -> impl Iterator<Item = Key> {
-        iter::successors(Some(key_to_coords(start)), |(x, y)| {
-            if *x == 1 || *y == 9 {
+    fn up_left_from(start: Key) -> impl Iterator<Item = Key> {
+        iter::successors(Some(key_to_coords(start)), |&Coord { x, y }| {
+            if x == 1 || y == 9 {
                 None
             } else {
-                Some((*x - 1, *y + 1))
+                Some(Coord::new(x - 1, y + 1))
             }
         })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(coords_to_key)
     }
     fn along_bottom_right() -> impl Iterator<Item = Key> {
-        (11..19).chain((19..=99).step_by(10))
+        (11..19).chain((19..=99).step_by(10)).map(Key)
     }
-    fn left_from(start: u8) -> impl Iterator<Item = Key> {
-        iter::successors(Some(key_to_coords(start)), |(x, y)| {
-            if *x == 1 {
+    fn left_from(start: Key) -> impl Iterator<Item = Key> {
+        iter::successors(Some(key_to_coords(start)), |&Coord { x, y }| {
+            if x == 1 {
                 None
             } else {
-                Some((*x - 1, *y))
+                Some(Coord::new(x - 1, y))
             }
         })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(coords_to_key)
     }
-    fn down_from(start: u8) -> impl Iterator<Item = Key> {
-        iter::successors(Some(key_to_coords(start)), |(x, y)| {
-            if *y == 1 {
+    fn down_from(start: Key) -> impl Iterator<Item = Key> {
+        iter::successors(Some(key_to_coords(start)), |&Coord { x, y }| {
+            if y == 1 {
                 None
             } else {
-                Some((*x, *y - 1))
+                Some(Coord::new(x, y - 1))
             }
         })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(coords_to_key)
     }
-    fn right_from(start: u8) -> impl Iterator<Item = Key> {
-        iter::successors(Some(key_to_coords(start)), |(x, y)| {
-            if *x == 9 {
+    fn right_from(start: Key) -> impl Iterator<Item = Key> {
+        iter::successors(Some(key_to_coords(start)), |&Coord { x, y }| {
+            if x == 9 {
                 None
             } else {
-                Some((*x + 1, *y))
+                Some(Coord::new(x + 1, y))
             }
         })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(coords_to_key)
     }
-    fn up_from(start: u8) -> impl Iterator<Item = Key> {
-        iter::successors(Some(key_to_coords(start)), |(x, y)| {
-            if *y == 9 {
+    fn up_from(start: Key) -> impl Iterator<Item = Key> {
+        iter::successors(Some(key_to_coords(start)), |&Coord { x, y }| {
+            if y == 9 {
                 None
             } else {
-                Some((*x, *y + 1))
+                Some(Coord::new(x, y + 1))
             }
         })
-        .map(|(x, y)| coords_to_key(x, y))
+        .map(coords_to_key)
+    }
+
+    // sleep for `duration`, or less if `cancel` is raised in the meantime, returning whether it was.
+    // it's checked every few milliseconds so that a cancellation never has long to wait.
+    fn wait(duration: Duration, cancel: &AtomicBool) -> bool {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        let t = Instant::now();
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return true;
+            }
+            let remaining = duration.saturating_sub(t.elapsed());
+            if remaining.is_zero() {
+                return false;
+            }
+            thread::sleep(remaining.min(POLL_INTERVAL));
+        }
+    }
+
+    // what a cancelled animation leaves behind, so that nothing is stuck half-drawn
+    fn clear(launchpad: &mut dyn LaunchpadDevice) -> eyre::Result<()> {
+        for key in grid_full() {
+            launchpad.send(&Command::KeyOff(key))?;
+        }
+        Ok(())
     }
 
-    pub(crate) fn startup(launchpad: &mut Launchpad) -> eyre::Result<()> {
+    // the blocking animations below all stop early (with the display cleared) once `cancel` is raised
+    pub(crate) fn startup(
+        launchpad: &mut dyn LaunchpadDevice,
+        cancel: &AtomicBool,
+    ) -> eyre::Result<()> {
         const STRIPES: &[u8] = &[
             0,
             TRANS_BLUE,
@@ -178,17 +404,22 @@ mod animations {
             if it.next().is_none() {
                 break;
             }
-            thread::sleep(DELAY.saturating_sub(t.elapsed()));
+            if wait(DELAY.saturating_sub(t.elapsed()), cancel) {
+                return clear(launchpad);
+            }
         }
 
         Ok(())
     }
 
-    pub(crate) fn shutdown(launchpad: &mut Launchpad) -> eyre::Result<()> {
+    pub(crate) fn shutdown(
+        launchpad: &mut dyn LaunchpadDevice,
+        cancel: &AtomicBool,
+    ) -> eyre::Result<()> {
         // clear the display, there may be garbage on it
         for x in 1..=9 {
             for y in 1..=9 {
-                launchpad.send(&Command::KeyOff(coords_to_key(x, y)))?;
+                launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x, y))))?;
             }
         }
 
@@ -217,7 +448,7 @@ mod animations {
                     (1, 55),
                 ]
                 .into_iter()
-                .map(Option::Some),
+                .map(|(n, key)| Some((n, Key(key)))),
             )
             .take(9 + STRIPES.len() - 1)
             .multipeek();
@@ -230,11 +461,11 @@ mod animations {
                         launchpad.send(&Command::KeyOn(key, SimpleColor::Static(*color)))?;
                         let mut x;
                         let mut y;
-                        (x, y) = key_to_coords(key);
+                        (x, y) = key_to_coords(key).into();
                         for _ in 0..3 {
                             (x, y) = ((-(y as i8 - 5) + 5) as u8, x);
                             launchpad.send(&Command::KeyOn(
-                                coords_to_key(x, y),
+                                coords_to_key(Coord::new(x, y)),
                                 SimpleColor::Static(*color),
                             ))?;
                         }
@@ -244,40 +475,143 @@ mod animations {
             if it.next().is_none() {
                 break;
             }
-            thread::sleep(DELAY.saturating_sub(t.elapsed()));
+            if wait(DELAY.saturating_sub(t.elapsed()), cancel) {
+                return clear(launchpad);
+            }
         }
 
         Ok(())
     }
 
+    // a calmer alternative to `shutdown`: dim everything down to nothing over `duration`, then
+    // clear the display. the brightness is put back afterwards (with nothing lit, so there's
+    // nothing to see), so that whatever uses the Launchpad next finds it as it was.
+    pub(crate) fn fade_out(
+        launchpad: &mut dyn LaunchpadDevice,
+        duration: Duration,
+        cancel: &AtomicBool,
+    ) -> eyre::Result<()> {
+        const STEPS: u32 = 20;
+        let brightness = launchpad.query_brightness().unwrap_or(127);
+        for i in (0..STEPS).rev() {
+            let t = Instant::now();
+            launchpad.send(&Command::SetBrightness((brightness as u32 * i / STEPS) as u8))?;
+            if wait((duration / STEPS).saturating_sub(t.elapsed()), cancel) {
+                break;
+            }
+        }
+        clear(launchpad)?;
+        launchpad.send(&Command::SetBrightness(brightness))?;
+        Ok(())
+    }
+
+    // unlike the others, these draw into a framebuffer a frame at a time instead of sending to the
+    // Launchpad and sleeping in between, so they can run while the UI carries on as usual
+    pub(crate) trait Animation {
+        // draw whatever should be showing now, returning false once there's nothing left to show
+        fn step(&mut self, fb: &mut Framebuffer) -> bool;
+    }
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub(crate) enum Direction {
+        Forwards,
+        Backwards,
+    }
+
+    pub(crate) struct PaletteCycle {
+        keys: Vec<Key>,
+        palette: Vec<u8>,
+        period: Duration,
+        direction: Direction,
+        wraps: bool,
+        start: Instant,
+    }
+
+    // shift the colours in `palette` along `region` (in the order it's given) by one key every
+    // `period`, like a marquee: a generalisation of the stripes in `startup`. by default it goes
+    // forwards and wraps around forever; without wrapping, the palette slides in from one end and
+    // the animation finishes once it's gone off the other.
+    pub(crate) fn palette_cycle(
+        region: impl IntoIterator<Item = Key>,
+        palette: &[u8],
+        period: Duration,
+    ) -> PaletteCycle {
+        assert!(!palette.is_empty());
+        PaletteCycle {
+            keys: region.into_iter().collect(),
+            palette: palette.to_vec(),
+            period,
+            direction: Direction::Forwards,
+            wraps: true,
+            start: Instant::now(),
+        }
+    }
+
+    impl PaletteCycle {
+        pub(crate) fn direction(mut self, direction: Direction) -> Self {
+            self.direction = direction;
+            self
+        }
+
+        pub(crate) fn wrapping(mut self, wraps: bool) -> Self {
+            self.wraps = wraps;
+            self
+        }
+    }
+
+    impl Animation for PaletteCycle {
+        fn step(&mut self, fb: &mut Framebuffer) -> bool {
+            let offset = (self.start.elapsed().as_nanos() / self.period.as_nanos().max(1)) as usize;
+            let (len, colors) = (self.keys.len(), self.palette.len());
+            for (i, key) in self.keys.iter().enumerate() {
+                let i = match self.direction {
+                    Direction::Forwards => i,
+                    Direction::Backwards => len - 1 - i,
+                };
+                // the first colour in the palette leads the way
+                let color = if self.wraps {
+                    Some(self.palette[(i + colors - offset % colors) % colors])
+                } else {
+                    offset.checked_sub(i).and_then(|j| self.palette.get(j).copied())
+                };
+                fb.set(*key, color.map_or(Color::OFF, Color::simple));
+            }
+            self.wraps || offset < len + colors
+        }
+    }
+
     // just flash the entire launchpad orange
     // TODO: this should be an animation emanating from the square responsible for the alert
-    pub(crate) fn alert(launchpad: &mut Launchpad, focus: Option<u8>) -> eyre::Result<()> {
+    pub(crate) fn alert(
+        launchpad: &mut dyn LaunchpadDevice,
+        focus: Option<Key>,
+        cancel: &AtomicBool,
+    ) -> eyre::Result<()> {
         let is_real_focus = focus.is_some();
-        let focus = focus.unwrap_or(55);
-        let (focus_x, focus_y) = key_to_coords(focus);
-        // let (focus_x, focus_y) = key_to_coords(55);
+        let focus = focus.unwrap_or(Key(55));
+        let Coord { x: focus_x, y: focus_y } = key_to_coords(focus);
+        // let Coord { x: focus_x, y: focus_y } = key_to_coords(55);
         const DELAY: Duration = Duration::from_millis(50);
 
         // first, clear the display
         for x in 1..=9 {
             for y in 1..=9 {
-                launchpad.send(&Command::KeyOff(coords_to_key(x, y)))?;
+                launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x, y))))?;
             }
         }
         // next, pulse the focused key (it will remain this way throughout)
-        launchpad.send(&Command::KeyOn(coords_to_key(focus_x, focus_y), SimpleColor::Pulsing(9)))?;
-        // launchpad.send(&Command::KeyOn(coords_to_key(focus_x, focus_y), SimpleColor::Static(9)))?;
+        launchpad.send(&Command::KeyOn(coords_to_key(Coord::new(focus_x, focus_y)), SimpleColor::Pulsing(9)))?;
+        // launchpad.send(&Command::KeyOn(coords_to_key(Coord::new(focus_x, focus_y)), SimpleColor::Static(9)))?;
 
-        fn light(launchpad: &mut Launchpad, x: i8, y: i8) -> eyre::Result<()> {
+        fn light(launchpad: &mut dyn LaunchpadDevice, x: i8, y: i8) -> eyre::Result<()> {
             if (1..=9).contains(&x) && (1..=9).contains(&y) {
-                launchpad.send(&Command::KeyOn(coords_to_key(x as u8, y as u8), SimpleColor::Static(9)))?;
+                launchpad.send(&Command::KeyOn(coords_to_key(Coord::new(x as u8, y as u8)), SimpleColor::Static(9)))?;
             }
             Ok(())
         }
-        fn extinguish(launchpad: &mut Launchpad, x: i8, y: i8) -> eyre::Result<()> {
+        fn extinguish(launchpad: &mut dyn LaunchpadDevice, x: i8, y: i8) -> eyre::Result<()> {
             if (1..=9).contains(&x) && (1..=9).contains(&y) {
-                launchpad.send(&Command::KeyOff(coords_to_key(x as u8, y as u8)))?;
+                launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x as u8, y as u8))))?;
             }
             Ok(())
         }
@@ -292,33 +626,35 @@ mod animations {
         let mut left = left_from(focus).skip(1);
         let mut right = right_from(focus).skip(1);
         loop {
-            thread::sleep(DELAY);
+            if wait(DELAY, cancel) {
+                return clear(launchpad);
+            }
             match (up.next(), down.next(), left.next(), right.next()) {
                 (None, None, None, None) => break,
                 (u, d, l, r) => {
-                    top_bound = u.map(|k| key_to_coords(k).1).unwrap_or(top_bound);
-                    bottom_bound = d.map(|k| key_to_coords(k).1).unwrap_or(bottom_bound);
-                    left_bound = l.map(|k| key_to_coords(k).0).unwrap_or(left_bound);
-                    right_bound = r.map(|k| key_to_coords(k).0).unwrap_or(right_bound);
+                    top_bound = u.map(|k| key_to_coords(k).y).unwrap_or(top_bound);
+                    bottom_bound = d.map(|k| key_to_coords(k).y).unwrap_or(bottom_bound);
+                    left_bound = l.map(|k| key_to_coords(k).x).unwrap_or(left_bound);
+                    right_bound = r.map(|k| key_to_coords(k).x).unwrap_or(right_bound);
                     // println!("expanding: filling from ({}, {}) to ({}, {})", left_bound, top_bound, right_bound, bottom_bound);
                     for x in left_bound..=right_bound {
                         for y in bottom_bound..=top_bound {
                             if x != focus_x || y != focus_y {
-                                launchpad.send(&Command::KeyOn(coords_to_key(x, y), SimpleColor::Static(9)))?;
+                                launchpad.send(&Command::KeyOn(coords_to_key(Coord::new(x, y)), SimpleColor::Static(9)))?;
                             }
                         }
                     }
                 }
             }
         }
-        // for (i, top_left) in up_left_from(coords_to_key(focus_x, focus_y)).enumerate().skip(1) {
+        // for (i, top_left) in up_left_from(coords_to_key(Coord::new(focus_x, focus_y))).enumerate().skip(1) {
         //     thread::sleep(DELAY);
         //     // println!("expanding: {:?}, top_left {:?}", i, top_left);
         //     for x_off in 0..=(i*2) {
         //         for y_off in 0..=(i*2) {
-        //             let (x, y) = (key_to_coords(top_left).0 + x_off as u8, key_to_coords(top_left).1 - y_off as u8);
+        //             let (x, y) = (key_to_coords(top_left).x + x_off as u8, key_to_coords(top_left).y - y_off as u8);
         //             if x != focus_x || y != focus_y {
-        //                 // launchpad.send(&Command::KeyOn(coords_to_key(x, y), SimpleColor::Static(9)))?;
+        //                 // launchpad.send(&Command::KeyOn(coords_to_key(Coord::new(x, y)), SimpleColor::Static(9)))?;
         //                 light(launchpad, x as i8, y as i8)?;
         //             }
         //         }
@@ -326,7 +662,9 @@ mod animations {
         // }
 
         // phase 2: wait a bit
-        thread::sleep(Duration::from_millis(900));
+        if wait(Duration::from_millis(900), cancel) {
+            return clear(launchpad);
+        }
 
         // phase 3: contract back towards the focus
         let mut top_bound = focus_y;
@@ -344,28 +682,28 @@ mod animations {
                 (None, None, None, None) => break,
                 (u, d, l, r) => {
                     bounds.push((u, d, l, r));
-                    // top_bound = u.map(|k| key_to_coords(k).1).unwrap_or(top_bound);
-                    // bottom_bound = d.map(|k| key_to_coords(k).1).unwrap_or(bottom_bound);
-                    // left_bound = l.map(|k| key_to_coords(k).0).unwrap_or(left_bound);
-                    // right_bound = r.map(|k| key_to_coords(k).0).unwrap_or(right_bound);
+                    // top_bound = u.map(|k| key_to_coords(k).y).unwrap_or(top_bound);
+                    // bottom_bound = d.map(|k| key_to_coords(k).y).unwrap_or(bottom_bound);
+                    // left_bound = l.map(|k| key_to_coords(k).x).unwrap_or(left_bound);
+                    // right_bound = r.map(|k| key_to_coords(k).x).unwrap_or(right_bound);
                     // let mut batch = Vec::new();
                     // if l.is_some() && r.is_some() {
                     //     for x in left_bound..=right_bound {
                     //         if d.is_some() {
-                    //             batch.push(Command::KeyOff(coords_to_key(x, bottom_bound)));
+                    //             batch.push(Command::KeyOff(coords_to_key(Coord::new(x, bottom_bound))));
                     //         }
                     //         if u.is_some() {
-                    //             batch.push(Command::KeyOff(coords_to_key(x, top_bound)));
+                    //             batch.push(Command::KeyOff(coords_to_key(Coord::new(x, top_bound))));
                     //         }
                     //     }
                     // }
                     // if d.is_some() && u.is_some() {
                     //     for y in bottom_bound..=top_bound {
                     //         if l.is_some() {
-                    //             batch.push(Command::KeyOff(coords_to_key(left_bound, y)));
+                    //             batch.push(Command::KeyOff(coords_to_key(Coord::new(left_bound, y))));
                     //         }
                     //         if r.is_some() {
-                    //             batch.push(Command::KeyOff(coords_to_key(right_bound, y)));
+                    //             batch.push(Command::KeyOff(coords_to_key(Coord::new(right_bound, y))));
                     //         }
                     //     }
                     // }
@@ -376,25 +714,27 @@ mod animations {
         for (u, d, l, r) in bounds.into_iter().rev() {
             if let Some(u) = u {
                 for x in 1..=9 {
-                    launchpad.send(&Command::KeyOff(coords_to_key(x, key_to_coords(u).1)))?;
+                    launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x, key_to_coords(u).y))))?;
                 }
             }
             if let Some(d) = d {
                 for x in 1..=9 {
-                    launchpad.send(&Command::KeyOff(coords_to_key(x, key_to_coords(d).1)))?;
+                    launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x, key_to_coords(d).y))))?;
                 }
             }
             if let Some(l) = l {
                 for y in 1..=9 {
-                    launchpad.send(&Command::KeyOff(coords_to_key(key_to_coords(l).0, y)))?;
+                    launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(key_to_coords(l).x, y))))?;
                 }
             }
             if let Some(r) = r {
                 for y in 1..=9 {
-                    launchpad.send(&Command::KeyOff(coords_to_key(key_to_coords(r).0, y)))?;
+                    launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(key_to_coords(r).x, y))))?;
                 }
             }
-            thread::sleep(DELAY);
+            if wait(DELAY, cancel) {
+                return clear(launchpad);
+            }
         }
         // for batch in commands.into_iter().rev() {
         //     dbg!(&batch);
@@ -405,13 +745,13 @@ mod animations {
         //     }
         //     thread::sleep(DELAY);
         // }
-        // for (i, top_left) in up_left_from(coords_to_key(focus_x, focus_y)).enumerate().skip(1).collect_vec().into_iter().rev() {
+        // for (i, top_left) in up_left_from(coords_to_key(Coord::new(focus_x, focus_y))).enumerate().skip(1).collect_vec().into_iter().rev() {
         //     // println!("contracting: {:?}, top_left {:?}", i, top_left);
         //     for x_off in 0..=(i*2) {
         //         for y_off in 0..=(i*2) {
-        //             let (x, y) = (key_to_coords(top_left).0 + x_off as u8, key_to_coords(top_left).1 - y_off as u8);
+        //             let (x, y) = (key_to_coords(top_left).x + x_off as u8, key_to_coords(top_left).y - y_off as u8);
         //             if (x != focus_x || y != focus_y) && (x_off == 0 || x_off == i*2 || y_off == 0 || y_off == i*2) {
-        //                 // launchpad.send(&Command::KeyOff(coords_to_key(x, y)))?;
+        //                 // launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(x, y))))?;
         //                 extinguish(launchpad, x as i8, y as i8)?;
         //             }
         //         }
@@ -421,22 +761,107 @@ mod animations {
 
         // clean up the pulsing focus (unless it's wanted by the caller)
         if !is_real_focus {
-            launchpad.send(&Command::KeyOff(coords_to_key(focus_x, focus_y)))?;
+            launchpad.send(&Command::KeyOff(coords_to_key(Coord::new(focus_x, focus_y))))?;
         }
 
         Ok(())
     }
+
+    // red through to purple, one for each column (including the buttons down the side)
+    const RAINBOW: &[u8] = &[5, 9, 13, 17, 21, 33, 41, 45, 49];
+
+    // draw a framebuffer animation for `duration` (or until it finishes, or is cancelled),
+    // returning whether it was cancelled
+    fn run(
+        launchpad: &mut dyn LaunchpadDevice,
+        animation: &mut dyn Animation,
+        duration: Duration,
+        cancel: &AtomicBool,
+    ) -> eyre::Result<bool> {
+        const DELAY: Duration = Duration::from_millis(50);
+        let start = Instant::now();
+        let mut fb = Framebuffer::new();
+        while start.elapsed() < duration {
+            let t = Instant::now();
+            fb.clear();
+            let more = animation.step(&mut fb);
+            launchpad.full_update(&fb)?;
+            if !more {
+                break;
+            }
+            if wait(DELAY.saturating_sub(t.elapsed()), cancel) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // a bit of everything, over and over until `cancel` is raised: the startup stripes, a palette
+    // cycle, a rainbow, the alert, and a scale on the piano (lit along the bottom row). `play` is
+    // told which MIDI note to start or stop, and can do nothing if there's no sound to be had.
+    pub(crate) fn demo(
+        launchpad: &mut dyn LaunchpadDevice,
+        cancel: &AtomicBool,
+        play: &mut dyn FnMut(u8, bool),
+    ) -> eyre::Result<()> {
+        const MAJOR_SCALE: &[u8] = &[0, 2, 4, 5, 7, 9, 11, 12];
+        const NOTE_LENGTH: Duration = Duration::from_millis(250);
+        loop {
+            startup(launchpad, cancel)?;
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let mut cycle = palette_cycle(grid_full(), RAINBOW, Duration::from_millis(100));
+            if run(launchpad, &mut cycle, Duration::from_secs(3), cancel)? {
+                break;
+            }
+
+            let mut fb = Framebuffer::new();
+            for key in grid_full() {
+                let Coord { x, .. } = key_to_coords(key);
+                fb.set(key, Color::simple(RAINBOW[x as usize - 1]));
+            }
+            launchpad.full_update(&fb)?;
+            if wait(Duration::from_secs(2), cancel) {
+                break;
+            }
+
+            alert(launchpad, None, cancel)?;
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            // middle C upwards
+            let mut cancelled = false;
+            for (key, interval) in (11..).map(Key).zip(MAJOR_SCALE) {
+                let note = 60 + interval;
+                launchpad.send(&Command::KeyOn(key, SimpleColor::Static(21)))?;
+                play(note, true);
+                cancelled = wait(NOTE_LENGTH, cancel);
+                play(note, false);
+                launchpad.send(&Command::KeyOff(key))?;
+                if cancelled {
+                    break;
+                }
+            }
+            if cancelled {
+                break;
+            }
+        }
+        clear(launchpad)
+    }
 }
 
-fn _stress_test(launchpad: &mut Launchpad) -> eyre::Result<()> {
+fn _stress_test(launchpad: &mut dyn LaunchpadDevice) -> eyre::Result<()> {
     let mut vec_a = vec![];
     let mut vec_b = vec![];
     let mut vec_c = vec![];
     for i in 11..=19 {
         for j in 0..=8 {
-            vec_a.push((i + 10 * j, ComplexColor::Rgb(127, 0, 0)));
-            vec_b.push((i + 10 * j, ComplexColor::Rgb(0, 127, 0)));
-            vec_c.push((i + 10 * j, ComplexColor::Rgb(0, 0, 127)));
+            vec_a.push((Key(i + 10 * j), ComplexColor::Rgb(127, 0, 0)));
+            vec_b.push((Key(i + 10 * j), ComplexColor::Rgb(0, 127, 0)));
+            vec_c.push((Key(i + 10 * j), ComplexColor::Rgb(0, 0, 127)));
         }
     }
     let a = Command::SetColors(&vec_a);
@@ -451,10 +876,32 @@ fn _stress_test(launchpad: &mut Launchpad) -> eyre::Result<()> {
         launchpad.send(&c)?;
         thread::sleep(Duration::from_micros(US));
     }
-    launchpad.send(&Command::KeyOn(55, SimpleColor::Static(13)))?;
+    launchpad.send(&Command::KeyOn(Key(55), SimpleColor::Static(13)))?;
     Ok(())
 }
 
+/// How a `Ui::button` behaves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ButtonMode {
+    /// On for as long as the key is held down.
+    Momentary,
+    /// Flips between on and off each time the key is pressed.
+    Latching,
+    /// On just once, as the key is pressed.
+    Impulse,
+}
+
+/// What happened to a `Ui::button` this frame.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct ButtonResult {
+    /// The key was pressed this frame.
+    pressed_edge: bool,
+    /// The key was released this frame.
+    released_edge: bool,
+    /// Whether the button is on, according to its mode.
+    is_on: bool,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 #[non_exhaustive]
 enum Event {
@@ -464,9 +911,138 @@ enum Event {
     I3,
     MediaPlaying(bool),
     Redraw,
+    /// The metronome ticked; this is the number of the beat within the bar.
+    Beat(usize),
+    /// Something the Launchpad said that nothing here is interested in.
+    Other(Message),
+    /// The start of a new minute, for the clock.
+    Tick,
+    /// The status checks came out differently from last time.
+    Status(StatusCache),
+    /// Some DAW took the Launchpad over (or gave it back).
+    DawMode(bool),
     Exit,
 }
 
+/// What i3 last said about its workspaces and outputs, refetched on every `Event::I3`.
+#[derive(Default)]
+struct WorkspaceModel {
+    outputs: Vec<i3_ipc::reply::Output>,
+    /// The numbers of the workspaces on each output, by output name.
+    per_output: HashMap<String, Vec<i32>>,
+    by_num: HashMap<i32, i3_ipc::reply::Workspace>,
+}
+
+impl WorkspaceModel {
+    fn fetch(i3: &mut I3) -> eyre::Result<Self> {
+        let mut per_output = HashMap::new();
+        let mut by_num = HashMap::new();
+        for workspace in i3.get_workspaces()? {
+            per_output
+                .entry(workspace.output.clone())
+                .or_insert(Vec::new())
+                .push(workspace.num);
+            by_num.entry(workspace.num).or_insert(workspace);
+        }
+        Ok(WorkspaceModel {
+            outputs: i3.get_outputs()?,
+            per_output,
+            by_num,
+        })
+    }
+
+    /// The workspace with keyboard focus, if i3 says there is one.
+    fn focused(&self) -> Option<&i3_ipc::reply::Workspace> {
+        self.by_num.values().find(|w| w.focused)
+    }
+}
+
+/// What the status checks (which shell out, so they're too slow to run while drawing) last found.
+/// They're rerun in the background every `REDRAW_INTERVAL`, and whenever pactl says something has
+/// started or stopped recording, with an `Event::Status` whenever the answer changes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct StatusCache {
+    /// Whether the USB microphone is plugged in.
+    microphone_plugged_in: bool,
+    /// Whether anything is recording from whichever microphone is in use.
+    recording: bool,
+}
+
+impl StatusCache {
+    /// A check that can't be run counts as a no.
+    fn fetch() -> Self {
+        let run = |what: &str, result: std::io::Result<bool>| {
+            result.unwrap_or_else(|e| {
+                eprintln!("warning: couldn't check {}: {}", what, e);
+                false
+            })
+        };
+        StatusCache {
+            microphone_plugged_in: run(
+                "for the microphone",
+                process::Command::new("lsusb")
+                    .arg("-d")
+                    .arg("17a0:0304")
+                    .stdout(process::Stdio::null())
+                    .status()
+                    .map(|status| status.success()),
+            ),
+            recording: run(
+                "whether anything is recording",
+                process::Command::new("pactl")
+                    .arg("list")
+                    .arg("short")
+                    .arg("source-outputs")
+                    .output()
+                    .map(|output| !output.stdout.is_empty()),
+            ),
+        }
+    }
+
+    /// Off if the microphone is plugged in, otherwise lit up, and flashing if something's recording.
+    fn microphone_indicator(&self) -> Color {
+        if self.microphone_plugged_in {
+            Color::OFF
+        } else if !self.recording {
+            Color::simple(9)
+        } else {
+            Color::flashing(9, 0)
+        }
+    }
+}
+
+/// Which way a D-pad (see `Ui::dpad`) was pushed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// The order that `Ui::dpad_keys` takes its keys in.
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    /// What i3 calls it.
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        }
+    }
+}
+
+/// The arrow buttons along the top, as up, down, left and right.
+const ARROW_KEYS: [Key; 4] = [Key(91), Key(92), Key(93), Key(94)];
+
 // #[derive(Clone, Copy, Debug)]
 // enum Direction {
 //     Right,
@@ -491,7 +1067,7 @@ enum Event {
 //                 }
 //             }
 //         })
-//         .map(|(x, y)| coords_to_key(x, y))
+//         .map(|(x, y)| coords_to_key(Coord::new(x, y)))
 //     }
 // }
 
@@ -514,7 +1090,9 @@ enum Event {
 fn main() -> eyre::Result<()> {
     let (tx, rx) = mpsc::channel();
 
-    configure_signals(tx.clone()).wrap_err("couldn't set up signal handlers")?;
+    let cancel_animation = Arc::new(AtomicBool::new(false));
+    configure_signals(tx.clone(), Arc::clone(&cancel_animation))
+        .wrap_err("couldn't set up signal handlers")?;
 
     thread::spawn({
         let tx = tx.clone();
@@ -526,7 +1104,11 @@ fn main() -> eyre::Result<()> {
                 },
                 I3Stream,
             };
-            let mut i3 = I3Stream::conn_sub(&[Workspace, Output]).unwrap();
+            // if i3 isn't running, `main` will already have said so
+            let mut i3 = match I3Stream::conn_sub(&[Workspace, Output]) {
+                Ok(i3) => i3,
+                Err(_) => return,
+            };
             for event in i3.iter() {
                 match event.unwrap() {
                     Event::Workspace(_) => tx.send(crate::Event::I3).unwrap(),
@@ -545,10 +1127,19 @@ fn main() -> eyre::Result<()> {
     thread::spawn({
         let tx = tx.clone();
         move || {
-            for line in
-                BufReader::new(duct::cmd!("playerctl", "-F", "status").unchecked().reader().unwrap()).lines()
-            {
-                match line.unwrap().as_str() {
+            let reader = match playerctl(PLAYER, &["-F", "status"]).unchecked().reader() {
+                Ok(reader) => reader,
+                Err(error) => {
+                    eprintln!("warning: couldn't follow playerctl: {}", error);
+                    return;
+                }
+            };
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                match line.as_str() {
                     "Playing" => tx.send(Event::MediaPlaying(true)).unwrap(),
                     "Paused" | "Stopped" => tx.send(Event::MediaPlaying(false)).unwrap(),
                     "" => {}
@@ -558,27 +1149,131 @@ fn main() -> eyre::Result<()> {
         }
     });
 
+    thread::spawn({
+        let tx = tx.clone();
+        move || loop {
+            thread::sleep(REDRAW_INTERVAL);
+            tx.send(Event::Redraw).unwrap();
+        }
+    });
+
+    let (refresh_status, refresh_status_rx) = mpsc::channel();
+    thread::spawn(move || {
+        // lines look like "Event 'new' on source-output #123"
+        let reader = match duct::cmd("pactl", ["subscribe"]).unchecked().reader() {
+            Ok(reader) => reader,
+            Err(error) => {
+                eprintln!("warning: couldn't subscribe to pactl: {}", error);
+                return;
+            }
+        };
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.contains(" on source-output ") {
+                refresh_status.send(()).unwrap();
+            }
+        }
+    });
+
     thread::spawn({
         let tx = tx.clone();
         move || {
-            const T: Duration = Duration::from_secs(10);
+            let mut last = None;
             loop {
-                thread::sleep(T);
-                tx.send(Event::Redraw).unwrap();
+                let status = StatusCache::fetch();
+                if last != Some(status) {
+                    tx.send(Event::Status(status)).unwrap();
+                    last = Some(status);
+                }
+                // whichever comes first. if pactl's gone away, this just carries on polling.
+                if let Err(mpsc::RecvTimeoutError::Disconnected) =
+                    refresh_status_rx.recv_timeout(REDRAW_INTERVAL)
+                {
+                    thread::sleep(REDRAW_INTERVAL);
+                }
             }
         }
     });
 
-    let mut launchpad = {
+    thread::spawn({
         let tx = tx.clone();
-        Launchpad::connect(move |_ts, message| match message {
-            Message::KeyDown(key) => tx.send(Event::KeyDown(key)).unwrap(),
+        move || {
+            let beat = Duration::from_secs(60) / METRONOME_BPM;
+            let mut next = Instant::now();
+            for n in (0..METRONOME_ACCENTS.len()).cycle() {
+                // keep to the schedule, rather than drifting by however long each beat takes
+                next += beat;
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+                if METRONOME_RUNNING.load(Ordering::Relaxed) {
+                    tx.send(Event::Beat(n)).unwrap();
+                    thread::sleep(METRONOME_FLASH);
+                    tx.send(Event::Redraw).unwrap();
+                }
+            }
+        }
+    });
+
+    thread::spawn({
+        let tx = tx.clone();
+        move || loop {
+            // every time zone is a whole number of minutes off UTC, so this is on the minute
+            // wherever we are
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            thread::sleep(Duration::from_secs(60 - now.as_secs() % 60));
+            tx.send(Event::Tick).unwrap();
+        }
+    });
+
+    let animation_ticker = AnimationTicker::spawn(tx.clone(), ANIMATION_FRAME_INTERVAL)?;
+
+    let mut launchpad: Box<dyn LaunchpadDevice> = {
+        let tx = tx.clone();
+        let cancel_animation = Arc::clone(&cancel_animation);
+        let callback = move |_ts: u64, message: Message| match message {
+            Message::KeyDown(key) => {
+                if DEMO_RUNNING.load(Ordering::Relaxed) {
+                    cancel_animation.store(true, Ordering::Relaxed);
+                }
+                tx.send(Event::KeyDown(key)).unwrap()
+            }
             Message::KeyUp(key) => tx.send(Event::KeyUp(key)).unwrap(),
-            Message::ProgrammerMode(_) => {}
+            Message::TextScrollFinished => {
+                TEXT_SCROLLING.lock().remove(&MAIN_BOARD);
+            }
+            Message::ProgrammerMode(_)
+            | Message::ApplicationVersion(_)
+            | Message::LedEcho(..) => {}
             Message::Brightness(brightness) => tx.send(Event::Brightness(brightness)).unwrap(),
-            message => unimplemented!("{:?}", message),
-        })
-        .wrap_err("couldn't connect to Launchpad")?
+            Message::DawMode(active) => tx.send(Event::DawMode(active)).unwrap(),
+            // the Launchpad can volunteer things (or answer a query sent from somewhere else), so
+            // this mustn't take the input thread down
+            message => tx.send(Event::Other(message)).unwrap(),
+        };
+        #[cfg(not(feature = "virtual"))]
+        let launchpad =
+            lp::Launchpad::connect(callback.clone()).wrap_err("couldn't connect to Launchpad")?;
+        #[cfg(feature = "virtual")]
+        let launchpad = lp::terminal::VirtualLaunchpad::connect(callback.clone())
+            .wrap_err("couldn't start virtual Launchpad")?;
+        // `--mirror` shows everything on another Launchpad as well (the second one plugged in, or
+        // with a virtual one, the first), and takes presses from both. if either stops working,
+        // the other carries on by itself.
+        if env::args().skip(1).any(|arg| arg == "--mirror") {
+            let index = if cfg!(feature = "virtual") { 0 } else { 1 };
+            let mirror = lp::Launchpad::connect_nth(index, callback)
+                .wrap_err("couldn't connect to the Launchpad to mirror to")?;
+            let mut tee = TeeDevice::new(Box::new(launchpad), Box::new(mirror));
+            tee.on_failure(|i, error| {
+                let which = if i == 0 { "the main Launchpad" } else { "the mirror" };
+                eprintln!("warning: {} stopped working, carrying on without it: {}", which, error);
+            });
+            Box::new(tee)
+        } else {
+            Box::new(launchpad)
+        }
     };
 
     // thread::Builder::new()
@@ -590,45 +1285,228 @@ fn main() -> eyre::Result<()> {
     //     })?;
 
     launchpad.send(&Command::SetAwake(true))?;
-    animations::startup(&mut launchpad).wrap_err("couldn't display startup animation")?;
-    // discard events that arrived during the startup animation
+    animations::startup(launchpad.as_mut(), &cancel_animation)
+        .wrap_err("couldn't display startup animation")?;
+    // discard events that arrived during the startup animation, except a request to leave again
     for _ in rx.try_iter() {}
-    tx.send(Event::Redraw)?;
-
-    // let mut keypad = [false; 100];
-    let mut keypad = HashMap::with_capacity(81);
-    for key in rect(11, 88) {
-        keypad.insert(key, false);
+    if cancel_animation.load(Ordering::Relaxed) {
+        tx.send(Event::Exit)?;
     }
+    tx.send(Event::Redraw)?;
 
-    let mut fb = HashMap::with_capacity(81);
-    for key in rect(11, 99) {
-        fb.insert(key, Color::Simple(SimpleColor::Static(0)));
-    }
+    let mut fb = Framebuffer::new();
+    let mut screensaver =
+        animations::palette_cycle(grid_8x8(), SCREENSAVER_PALETTE, SCREENSAVER_PERIOD)
+            .direction(animations::Direction::Backwards)
+            .wrapping(true);
 
     // let mixer = Arc::new(Mutex::new(usfx::Mixer::default()));
     // mixer.play(sample);
+    // without somewhere to play, there's no synth tab (and nothing makes a sound), but everything
+    // else still works
     let host = cpal::default_host();
-    let device = host.default_output_device().unwrap();
-    let config = device.default_output_config().unwrap();
+    let output = match host.default_output_device() {
+        Some(device) => match device.default_output_config() {
+            Ok(config) => Some((device, config)),
+            Err(error) => {
+                eprintln!("warning: couldn't configure the audio device, so the synth tab is disabled: {}", error);
+                None
+            }
+        },
+        None => {
+            eprintln!("warning: there's no audio device, so the synth tab is disabled");
+            None
+        }
+    };
+    let has_audio = output.is_some();
+    // how a note fades out once it's let go of; a release of zero cuts it off straight away. the
+    // fading happens in the audio callback whatever the UI is doing, so a note released by
+    // switching tabs finishes fading just the same.
+    #[derive(Clone, Copy)]
+    struct Envelope {
+        release: Duration,
+    }
+    impl Envelope {
+        const SUSTAINED: Envelope = Envelope {
+            release: Duration::from_secs(1),
+        };
+        // 2500 samples, which is what every note used to get
+        const SHORT: Envelope = Envelope {
+            release: Duration::from_micros(56_689),
+        };
+        // how much to turn the volume down by each sample (at `sample_rate`) while releasing
+        fn release_step(&self, sample_rate: f32) -> f32 {
+            if self.release.is_zero() {
+                1.0
+            } else {
+                1.0 / (self.release.as_secs_f32() * sample_rate)
+            }
+        }
+    }
     struct NoteState {
         input: bool,
         volume: f32,
         clock: f32,
         freq: f32,
+        envelope: Envelope,
     }
     impl NoteState {
-        fn new(freq: f32) -> Self {
+        fn new(freq: f32, envelope: Envelope) -> Self {
             NoteState {
                 input: false,
                 volume: 0.0,
                 clock: 0.0,
                 freq,
+                envelope,
             }
         }
     }
+    // equal temperament, with A4 (MIDI note 69) at 440Hz
+    fn note_to_freq(note: u8) -> f32 {
+        440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+    }
+    // which notes of the octave the in-key layout plays, as semitones above the root
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    enum Scale {
+        Chromatic,
+        Major,
+        NaturalMinor,
+        MajorPentatonic,
+        MinorPentatonic,
+        Blues,
+    }
+    impl Scale {
+        const ALL: [Scale; 6] = [
+            Scale::Chromatic,
+            Scale::Major,
+            Scale::NaturalMinor,
+            Scale::MajorPentatonic,
+            Scale::MinorPentatonic,
+            Scale::Blues,
+        ];
+        fn intervals(self) -> &'static [u8] {
+            match self {
+                Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+                Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+                Scale::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10],
+                Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+                Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+                Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            }
+        }
+        // how many degrees each row is above the one below it: as near to a fourth as the scale
+        // allows, like a guitar (or the Launchpad's own note mode)
+        fn row_offset(self) -> usize {
+            match self {
+                Scale::Chromatic => 5,
+                Scale::MajorPentatonic | Scale::MinorPentatonic => 2,
+                Scale::Major | Scale::NaturalMinor | Scale::Blues => 3,
+            }
+        }
+        // the MIDI note on the key at (x, y) in an isomorphic layout of this scale, with `lowest`
+        // (which should be a root) in the bottom left corner
+        fn note_at(self, lowest: u8, x: u8, y: u8) -> u8 {
+            let intervals = self.intervals();
+            let degree = (x as usize - 1) + (y as usize - 1) * self.row_offset();
+            lowest + 12 * (degree / intervals.len()) as u8 + intervals[degree % intervals.len()]
+        }
+    }
+    const NOTE_NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    // the C that the in-key layout's bottom row starts from (plus the root), an octave below middle
+    // C to match the lowest row of the keyboard
+    const IN_KEY_LOWEST_C: u8 = 48;
+    // which frequency each key plays, for `Ui::keyboard`. keys that aren't in it don't play at all,
+    // so a map can leave room for other buttons.
+    struct KeyMap {
+        freqs: HashMap<Key, f32>,
+    }
+    // a name to show, and how to make it
+    type KeyMapPreset = (&'static str, fn() -> KeyMap);
+    impl KeyMap {
+        // the ones the keyboard tab switches between
+        const PRESETS: [KeyMapPreset; 3] = [
+            ("piano", || KeyMap::pianos(48)),
+            ("guitar", KeyMap::guitar),
+            ("drums", || KeyMap::drum_pads(36)),
+        ];
+        fn from_notes(notes: impl IntoIterator<Item = (Key, u8)>) -> KeyMap {
+            KeyMap {
+                freqs: notes
+                    .into_iter()
+                    .map(|(key, note)| (key, note_to_freq(note)))
+                    .collect(),
+            }
+        }
+        // one octave of piano two rows high, with the white keys along row `y` from `lowest`
+        // (which should be a C) and the black keys above them
+        fn piano(y: u8, lowest: u8) -> KeyMap {
+            let white = (1..).zip([0, 2, 4, 5, 7, 9, 11, 12]).map(|(x, n)| (coords_to_key(Coord::new(x, y)), n));
+            let black = [(2, 1), (3, 3), (5, 6), (6, 8), (7, 10)]
+                .into_iter()
+                .map(|(x, n)| (coords_to_key(Coord::new(x, y + 1)), n));
+            KeyMap::from_notes(white.chain(black).map(|(key, n)| (key, lowest + n)))
+        }
+        // four pianos filling the grid, an octave apart from `lowest` at the bottom
+        fn pianos(lowest: u8) -> KeyMap {
+            let mut freqs = HashMap::new();
+            for octave in 0..4 {
+                freqs.extend(KeyMap::piano(1 + 2 * octave, lowest + 12 * octave).freqs);
+            }
+            KeyMap { freqs }
+        }
+        // six strings in standard tuning, low E along the bottom, with the open strings on the left
+        // and a fret to each key after that
+        fn guitar() -> KeyMap {
+            let strings = [40, 45, 50, 55, 59, 64];
+            KeyMap::from_notes((1..).zip(strings).flat_map(|(y, open)| {
+                (1..=GRID_SIZE).map(move |x| (coords_to_key(Coord::new(x, y)), open + x - 1))
+            }))
+        }
+        // a different note on every key of the grid, a semitone apart in reading order from the
+        // bottom left, like a drum rack
+        fn drum_pads(lowest: u8) -> KeyMap {
+            KeyMap::from_notes((lowest..).zip(grid_8x8()).map(|(note, key)| (key, note)))
+        }
+    }
+    // whether `freq` is nearest to a black key on a piano, so that a keyboard can be coloured in
+    fn is_accidental(freq: f32) -> bool {
+        let note = (69.0 + 12.0 * (freq / 440.0).log2()).round() as i32;
+        matches!(note.rem_euclid(12), 1 | 3 | 6 | 8 | 10)
+    }
     struct AudioState {
         notes: HashMap<usize, NoteState>,
+        // the most recent buffer of samples sent to the output, for drawing
+        output: Vec<f32>,
+        // like a sustain pedal: while it's down, notes keep sounding after their keys are let go
+        sustain: bool,
+        timbre: Timbre,
+        // of the output device, in Hz
+        sample_rate: f32,
+    }
+    impl AudioState {
+        // the key for note `id` is (or isn't) held down this frame
+        fn play(&mut self, id: usize, freq: f32, envelope: Envelope, held: bool) {
+            let sustain = self.sustain;
+            let note = self.notes.entry(id).or_insert_with(|| NoteState::new(freq, envelope));
+            // a note that's struck again gets whatever it's meant to be now, in case that's changed
+            if held && !note.input {
+                note.freq = freq;
+                note.envelope = envelope;
+            }
+            note.input = held || (sustain && note.input);
+        }
+        // letting go of the pedal lets go of everything it was holding on to. (anything still
+        // held down is picked up again by `play` straight afterwards.)
+        fn set_sustain(&mut self, sustain: bool) {
+            if self.sustain && !sustain {
+                for note in self.notes.values_mut() {
+                    note.input = false;
+                }
+            }
+            self.sustain = sustain;
+        }
     }
     fn get_audio_frame_static() -> f32 {
         static mut clock: f32 = 0.0;
@@ -641,7 +1519,21 @@ fn main() -> eyre::Result<()> {
             (440.0 * std::f32::consts::TAU * period).sin() * 0.2
         }
     }
+    // one sample of a note in `timbre`, `phase` radians into its fundamental
+    fn additive(timbre: Timbre, phase: f32) -> f32 {
+        let harmonics = timbre.harmonics();
+        let harmonics = &harmonics[..harmonics.len().min(MAX_HARMONICS)];
+        let total: f32 = harmonics.iter().sum();
+        let sum: f32 = (1..)
+            .zip(harmonics)
+            .filter(|(_, amplitude)| **amplitude != 0.0)
+            .map(|(n, amplitude)| (phase * n as f32).sin() * amplitude)
+            .sum();
+        sum / total
+    }
     fn get_audio_frame(audio_state: &mut AudioState) -> f32 {
+        let timbre = audio_state.timbre;
+        let sample_rate = audio_state.sample_rate;
         let mut value: f32 = 0.0;
         for (_, state) in audio_state.notes.iter_mut() {
             if state.input {
@@ -649,14 +1541,16 @@ fn main() -> eyre::Result<()> {
             }
             if state.volume > 0.0 {
                 state.clock += 1.0;
-                if state.clock >= 44100.0 {
+                if state.clock >= sample_rate {
                     state.clock = 0.0;
                 }
-                let period = state.clock / 44100.0;
-                let sample = (state.freq * std::f32::consts::TAU * period * 2.0).sin();
+                let period = state.clock / sample_rate;
+                let sample = additive(timbre, state.freq * std::f32::consts::TAU * period * 2.0);
                 // let sample = fast_sin((state.freq * std::f32::consts::TAU * period * 2.0) as f64);
                 value += sample as f32 * 0.2 * state.volume;
-                state.volume -= 0.0004;
+                if !state.input {
+                    state.volume = (state.volume - state.envelope.release_step(sample_rate)).max(0.0);
+                }
             } else {
                 state.clock = 0.0;
             }
@@ -676,49 +1570,87 @@ fn main() -> eyre::Result<()> {
         // active: false,
         // clock: 0.0,
         notes: HashMap::new(),
+        output: Vec::new(),
+        sustain: false,
+        timbre: SYNTH_TIMBRE,
+        // with no device, nothing's ever played, so any rate will do
+        sample_rate: output.as_ref().map_or(44100.0, |(_, config)| config.sample_rate().0 as f32),
     }));
-    let stream = device.build_output_stream(
-        &cpal::StreamConfig {
-            channels: 1,
-            sample_rate: cpal::SampleRate(44100),
-            buffer_size: cpal::BufferSize::Default,
-            // buffer_size: cpal::BufferSize::Fixed(2048 * 8),
-        },
-        {
-            // let mixer = mixer.clone();
-            // move |mut data: &mut [f32], _info| {
-            //     mixer.lock().generate(data);
-            // }
-            // https://github.com/0xC45/simple-synth/blob/42611692157830df9c17de10dd20abb4ee2806e1/src/main.rs#L236
-            let state = audio_state.clone();
-            move |data: &mut [f32], _info| {
-                for frame in data.chunks_mut(config.channels() as usize) {
-                    // let v = cpal::Sample::from::<f32>(&get_audio_frame());
-                    let v = get_audio_frame(&mut state.lock());
-                    // let v = get_audio_frame_static();
-                    for value in frame.iter_mut() {
-                        *value = v;
+    // the synth runs at whatever rate the device wants, with however many channels (it just plays
+    // the same thing on all of them). the stream's kept until the end, since dropping it stops
+    // the sound
+    let _stream = match &output {
+        Some((device, config)) => {
+            let stream_config = cpal::StreamConfig {
+                channels: config.channels(),
+                sample_rate: config.sample_rate(),
+                buffer_size: cpal::BufferSize::Default,
+                // buffer_size: cpal::BufferSize::Fixed(2048 * 8),
+            };
+            let channels = stream_config.channels as usize;
+            let stream = device.build_output_stream(
+                &stream_config,
+                {
+                    // let mixer = mixer.clone();
+                    // move |mut data: &mut [f32], _info| {
+                    //     mixer.lock().generate(data);
+                    // }
+                    // https://github.com/0xC45/simple-synth/blob/42611692157830df9c17de10dd20abb4ee2806e1/src/main.rs#L236
+                    let state = audio_state.clone();
+                    move |data: &mut [f32], _info| {
+                        let mut state = state.lock();
+                        state.output.clear();
+                        for frame in data.chunks_mut(channels) {
+                            // let v = cpal::Sample::from::<f32>(&get_audio_frame());
+                            let v = get_audio_frame(&mut state);
+                            // let v = get_audio_frame_static();
+                            state.output.push(v);
+                            for value in frame.iter_mut() {
+                                *value = v;
+                            }
+                        }
                     }
-                }
-            }
-        },
-        |error| Err(error).wrap_err("uhh").unwrap(),
-    )?;
-    stream.play()?;
-
-    let mut i3 = I3::connect()?;
-    let mut workspaces = i3.get_workspaces()?;
-    let mut outputs = i3.get_outputs()?;
-    let mut w_per_o = HashMap::new();
-    let mut w_by_num = HashMap::new();
-    for workspace in workspaces {
-        // TODO: yuuuuck
-        w_per_o
-            .entry(workspace.output.clone())
-            .or_insert(Vec::new())
-            .push(workspace.num);
-        w_by_num.entry(workspace.num).or_insert(workspace);
+                },
+                |error| Err(error).wrap_err("uhh").unwrap(),
+            )?;
+            stream.play()?;
+            Some(stream)
+        }
+        None => None,
+    };
+
+    // `--demo` shows off the animations (and the synth) before the UI proper starts
+    if env::args().skip(1).any(|arg| arg == "--demo") {
+        DEMO_RUNNING.store(true, Ordering::Relaxed);
+        animations::demo(launchpad.as_mut(), &cancel_animation, &mut |note, held| {
+            let freq = note_to_freq(note);
+            audio_state.lock().play(5000 + note as usize, freq, Envelope::SHORT, held);
+        })
+        .wrap_err("couldn't run the demo")?;
+        DEMO_RUNNING.store(false, Ordering::Relaxed);
+        // it was either a key press or a signal that stopped it, and only a signal leaves an exit
+        // behind. everything else that happened in the meantime is out of date now.
+        let exit = rx.try_iter().any(|event| event == Event::Exit);
+        for _ in rx.try_iter() {}
+        cancel_animation.store(false, Ordering::Relaxed);
+        if exit {
+            tx.send(Event::Exit)?;
+        }
+        tx.send(Event::Redraw)?;
     }
+
+    // without i3, there's no i3 tab, but everything else still works
+    let mut i3 = match I3::connect() {
+        Ok(i3) => Some(i3),
+        Err(error) => {
+            eprintln!("warning: couldn't connect to i3, so its tab is disabled: {}", error);
+            None
+        }
+    };
+    let mut workspaces = match &mut i3 {
+        Some(i3) => WorkspaceModel::fetch(i3)?,
+        None => WorkspaceModel::default(),
+    };
     const I3_COLORS: &[u8] = &[21, 29, 37, 45];
     let output_colors: HashMap<&str, u8> = [
         ("DP-1", 29u8),
@@ -727,61 +1659,164 @@ fn main() -> eyre::Result<()> {
         ("HDMI-2", 45u8),
     ].into_iter().collect();
 
+    let small_piano = KeyMap::piano(1, 60);
+    let no_piano = KeyMap { freqs: HashMap::new() };
+    let keymaps = KeyMap::PRESETS.map(|(name, preset)| (name, preset()));
+
+    let mut status = StatusCache::default();
+    let mut held_output = None;
+    // what the palette tab paints with
+    let mut brush = Color::simple(3);
     for event in rx.iter() {
         if let Event::Exit = event {
             break;
         }
-        if let Event::I3 = event {
-            // TODO: this i3 stuff is *awful*
-            workspaces = i3.get_workspaces()?;
-            outputs = i3.get_outputs()?;
-            w_per_o.clear();
-            w_by_num.clear();
-            for workspace in workspaces {
-                w_per_o
-                    .entry(workspace.output.clone())
-                    .or_insert(Vec::new())
-                    .push(workspace.num);
-                w_by_num.entry(workspace.num).or_insert(workspace);
+        if let Event::Other(message) = &event {
+            eprintln!("ignoring unexpected message from the Launchpad: {:?}", message);
+            continue;
+        }
+        // while a DAW has the grid, nothing drawn here shows up (although it's still kept track
+        // of). once it lets go, put programmer mode back and send everything again.
+        match event {
+            Event::DawMode(true) => {
+                eprintln!("a DAW has taken over the Launchpad, waiting for it to finish");
+                continue;
             }
+            Event::DawMode(false) => {
+                launchpad.send(&Command::SetProgrammerMode(true))?;
+                launchpad.resync()?;
+            }
+            _ => {}
         }
-        // "overdraw is bad"? nah that doesn't sound right
-        for key in rect(11, 99) {
-            *fb.get_mut(&key).unwrap() = Color::Simple(SimpleColor::Static(0));
+        if let (Event::I3, Some(i3)) = (&event, &mut i3) {
+            workspaces = WorkspaceModel::fetch(i3)?;
         }
+        if let Event::Status(new) = event {
+            status = new;
+        }
+        // "overdraw is bad"? nah that doesn't sound right
+        fb.clear();
+        // which Launchpad a `Ui` is drawing on, when there's more than one (as from
+        // `Launchpad::connect_nth`). widget state is kept in statics keyed by board, as well as by
+        // key and call site, so the same UI code drawn on two boards keeps a separate set for each:
+        // a button latched on one isn't latched on the other. (state that belongs to something
+        // outside the Launchpad, like whether music is playing, is still shared.)
+        type Board = usize;
+        // every widget of one kind's state, by board, key (or whatever else tells them apart), and
+        // call site
+        type WidgetState<T, K = Key> =
+            Lazy<Mutex<HashMap<(Board, K, &'static Location<'static>), T>>>;
+        // the same, for widgets that don't have a key of their own to keep apart by
+        type CallSiteState<T> = Lazy<Mutex<HashMap<(Board, &'static Location<'static>), T>>>;
         struct Ui<'a> {
-            fb: &'a mut HashMap<Key, Color>,
+            fb: &'a mut Framebuffer,
             event: Event,
-            launchpad_for_side_effects: &'a mut Launchpad,
+            // which board this is drawing on, see `Board`
+            board: Board,
+            launchpad_for_side_effects: &'a mut dyn LaunchpadDevice,
             tx_for_side_effects: &'a mpsc::Sender<Event>,
+            // the keys held down as of the start of this frame
+            held: Vec<Key>,
+            i3: Option<&'a mut I3>,
+            workspaces: &'a WorkspaceModel,
+            // raised to cut short any blocking animation a widget plays
+            cancel_animation: &'a AtomicBool,
+            // the output whose button (on the i3 tab) is held down, if any, so that pressing
+            // another one swaps their workspaces over
+            held_output: &'a mut Option<String>,
+            // raised by any widget that needs redrawing every so often (by the animation ticker)
+            // to do something without being pressed, like a repeat button that's held down
+            ticking: bool,
         }
+        // one tab's worth of UI
+        type Tab<'t, 'a> = &'t mut dyn FnMut(&mut Ui<'a>) -> eyre::Result<()>;
         impl<'a> Ui<'a> {
             /// A tabstrip widget.
             #[track_caller]
-            fn tabs<const LEN: u8>(&mut self, start: Key) -> u8 {
-                static DATA: Lazy<Mutex<HashMap<&Location, u8>>> = Lazy::new(|| {
+            fn tabs(&mut self, start: Key, len: u8) -> u8 {
+                static DATA: CallSiteState<u8> = Lazy::new(|| {
                     let m = HashMap::with_capacity(1);
                     Mutex::new(m)
                 });
                 let mut data = DATA.lock();
-                let tab = data.entry(Location::caller()).or_insert(0);
+                let tab = data.entry((self.board, Location::caller())).or_insert(0);
                 *tab = match self.event {
-                    Event::KeyDown(key) if key >= start && key < start + LEN => key - start,
+                    Event::KeyDown(key) if key >= start && key.0 < start.0 + len => key.0 - start.0,
+                    // there might be fewer tabs than there were last time
+                    _ if *tab >= len => 0,
                     _ => *tab,
                 };
-                for (i, k) in (start..start + LEN).enumerate() {
-                    *self.fb.get_mut(&k).unwrap() = if *tab == i as u8 {
+                for (i, k) in (start.0..start.0 + len).map(Key).enumerate() {
+                    let color = if *tab == i as u8 {
                         Color::Simple(SimpleColor::Static(20))
                     } else {
                         Color::Simple(SimpleColor::Static(1))
                     };
+                    self.fb.set(k, color);
                 }
                 *tab
             }
+            /// A tabstrip, along with the tabs themselves: whichever of `pages` is selected gets to draw the rest of the UI. Which pages
+            /// there are is up to the caller, so one that can't work (like the i3 tab without i3) can just be left out.
+            ///
+            /// Widget state lives in statics keyed by call site, so there's no list of which belongs to which tab. Instead, when switching away
+            /// from a tab, it's run once more for each key still held down, as though that key had been released, so that every widget in it
+            /// lets go (and anything held, like a synth note, stops) in the usual way. What that draws is thrown away.
+            ///
+            /// While page `i` is showing, page `ghost_of(i)` (if any) is drawn dimly behind it, so as not to lose track of it. The page on top
+            /// wins every cell it lights up; the ghost only shows through the ones it leaves off. The ghost is the page as it was drawn the
+            /// last time it was showing, since running it again underneath would set off its side effects too (like an urgent workspace's
+            /// alert), so it's only as up to date as that.
+            #[track_caller]
+            fn tab_pages(
+                &mut self,
+                start: Key,
+                pages: &mut [Tab<'_, 'a>],
+                ghost_of: impl Fn(usize) -> Option<usize>,
+            ) -> eyre::Result<()> {
+                static SHOWN: CallSiteState<usize> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                // the last frame of each page that's a ghost of some other one
+                static LAST_DRAWN: WidgetState<Framebuffer, usize> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let len = pages.len() as u8;
+                let tab = self.tabs(start, len) as usize;
+                let previous = SHOWN.lock().insert((self.board, Location::caller()), tab);
+                if let Some(previous) = previous.filter(|p| *p != tab && *p < pages.len()) {
+                    let event = std::mem::replace(&mut self.event, Event::Redraw);
+                    let fb = self.fb.clone();
+                    for key in self.held.clone() {
+                        if !(start.0..start.0 + len).contains(&key.0) {
+                            self.event = Event::KeyUp(key);
+                            (pages[previous])(self)?;
+                        }
+                    }
+                    *self.fb = fb;
+                    self.event = event;
+                }
+                let ghost = ghost_of(tab)
+                    .filter(|g| *g != tab && *g < pages.len())
+                    .and_then(|g| LAST_DRAWN.lock().get(&(self.board, g, Location::caller())).cloned());
+                (pages[tab])(self)?;
+                if (0..pages.len()).any(|i| i != tab && ghost_of(i) == Some(tab)) {
+                    LAST_DRAWN
+                        .lock()
+                        .insert((self.board, tab, Location::caller()), self.fb.clone());
+                }
+                if let Some(ghost) = ghost {
+                    // the tabstrip was drawn before either of them, and stays as it is
+                    for key in grid_full().filter(|k| !(start.0..start.0 + len).contains(&k.0)) {
+                        if self.fb[key].is_off() && !ghost[key].is_off() {
+                            self.fb.set_with_brightness(key, ghost[key], GHOST_BRIGHTNESS);
+                        }
+                    }
+                }
+                Ok(())
+            }
             /// A static, unchanging colour.
             #[track_caller]
             fn static_color(&mut self, key: Key, color: Color) {
-                *self.fb.get_mut(&key).unwrap() = color;
+                self.fb.set(key, color);
             }
             /// A toggleable button.
             #[track_caller]
@@ -791,31 +1826,43 @@ fn main() -> eyre::Result<()> {
                 inactive_color: Color,
                 active_color: Color,
             ) -> bool {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), bool>>> =
+                self.toggle_button_edge(key, inactive_color, active_color).0
+            }
+            /// A toggleable button, which also returns whether it was toggled this frame.
+            #[track_caller]
+            fn toggle_button_edge(
+                &mut self,
+                key: Key,
+                inactive_color: Color,
+                active_color: Color,
+            ) -> (bool, bool) {
+                static DATA: WidgetState<bool> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let enabled = data.entry((key, Location::caller())).or_insert(false);
-                *enabled = match self.event {
-                    Event::KeyDown(k) if k == key => !*enabled,
-                    _ => *enabled,
-                };
-                *self.fb.get_mut(&key).unwrap() = if *enabled {
-                    active_color
-                } else {
-                    inactive_color
-                };
-                *enabled
+                let enabled = data.entry((self.board, key, Location::caller())).or_insert(false);
+                let changed = self.event == Event::KeyDown(key);
+                if changed {
+                    *enabled = !*enabled;
+                }
+                self.fb.set(key, if *enabled { active_color } else { inactive_color });
+                (*enabled, changed)
             }
             /// A pair of buttons that decrement and increment a counter respectively.
             #[track_caller]
             fn counter_buttons<const MAX: i64>(&mut self, start: Key) -> i64 {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), i64>>> =
+                self.counter_buttons_edge::<MAX>(start).0
+            }
+            /// A pair of counter buttons, which also returns whether the counter changed this frame.
+            #[track_caller]
+            fn counter_buttons_edge<const MAX: i64>(&mut self, start: Key) -> (i64, bool) {
+                static DATA: WidgetState<i64> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let n = data.entry((start, Location::caller())).or_insert(0);
+                let n = data.entry((self.board, start, Location::caller())).or_insert(0);
+                let before = *n;
                 *n += match self.event {
                     Event::KeyDown(k) if k == start => -1,
-                    Event::KeyDown(k) if k == start + 1 => 1,
+                    Event::KeyDown(k) if k == Key(start.0 + 1) => 1,
                     _ => 0,
                 };
                 if *n == MAX {
@@ -823,52 +1870,281 @@ fn main() -> eyre::Result<()> {
                 } else if *n == -1 {
                     *n = MAX - 1;
                 }
-                *self.fb.get_mut(&start).unwrap() = match self.event {
+                let color = match self.event {
                     Event::KeyDown(k) if k == start => Color::Simple(SimpleColor::Static(2)),
                     _ => Color::Simple(SimpleColor::Static(1)),
                 };
-                *self.fb.get_mut(&(start + 1)).unwrap() = match self.event {
-                    Event::KeyDown(k) if k == start + 1 => Color::Simple(SimpleColor::Static(2)),
+                self.fb.set(start, color);
+                let color = match self.event {
+                    Event::KeyDown(k) if k == Key(start.0 + 1) => Color::Simple(SimpleColor::Static(2)),
                     _ => Color::Simple(SimpleColor::Static(1)),
                 };
-                *n
+                self.fb.set(Key(start.0 + 1), color);
+                (*n, *n != before)
+            }
+            /// Scroll `text` across the Launchpad once, returning whether it was sent.
+            /// Unless `interrupt` is set, nothing happens if some other text hasn't finished scrolling yet.
+            fn scroll_text(&mut self, text: &str, interrupt: bool) -> bool {
+                if !TEXT_SCROLLING.lock().insert(self.board) && !interrupt {
+                    return false;
+                }
+                self.launchpad_for_side_effects
+                    .send(&Command::ScrollText {
+                        loops: Some(false),
+                        speed: Some(15),
+                        color: Some(TextColor::Palette(3)),
+                        text: Some(text),
+                    })
+                    .unwrap();
+                true
+            }
+            /// Say that something went wrong, without giving up: `message` scrolls across once (pushing in front of anything else), and
+            /// `ERROR_KEY` lights up in `ERROR_COLOR` for a while (see `error_indicator`).
+            fn report_error(&mut self, message: &str) {
+                eprintln!("error: {}", message);
+                LAST_ERROR.lock().insert(self.board, Instant::now());
+                self.scroll_text(message, true);
+            }
+            /// Show `ERROR_KEY` in `ERROR_COLOR` if there's been an error in the last `ERROR_SHOWN_FOR`, drawing over whatever else is there.
+            /// Pressing it dismisses the error.
+            fn error_indicator(&mut self) {
+                let mut last_error = LAST_ERROR.lock();
+                if self.event == Event::KeyDown(ERROR_KEY) {
+                    last_error.remove(&self.board);
+                }
+                if let Some(time) = last_error.get(&self.board) {
+                    if time.elapsed() < ERROR_SHOWN_FOR {
+                        self.fb.set(ERROR_KEY, ERROR_COLOR);
+                    } else {
+                        last_error.remove(&self.board);
+                    }
+                }
             }
             /// A button that displays text when pressed.
             #[track_caller]
             fn info_button(&mut self, key: Key, color: Color, text: &str) {
-                *self.fb.get_mut(&key).unwrap() = color;
+                self.fb.set(key, color);
                 if let Event::KeyDown(k) = self.event {
                     if k == key {
-                        self.launchpad_for_side_effects
-                            .send(&Command::ScrollText {
-                                loops: Some(false),
-                                speed: Some(15),
-                                color: Some(TextColor::Palette(3)),
-                                text: Some(text),
-                            })
-                            .unwrap()
+                        self.scroll_text(text, true);
+                    }
+                }
+            }
+            /// A clock, which can be switched on and off: while it's on, the time (formatted according to `format`, as for `date`) scrolls
+            /// across at the start of every minute, unless something else is scrolling already. Pressing it to switch it on shows the time straight away.
+            #[track_caller]
+            fn clock(&mut self, key: Key, off_color: Color, on_color: Color, format: &str) {
+                let result = self.button(key, ButtonMode::Latching, off_color, on_color);
+                if result.is_on && (result.pressed_edge || self.event == Event::Tick) {
+                    match duct::cmd("date", [format!("+{}", format)]).read() {
+                        // the user asked for it, so it can push in
+                        Ok(time) => {
+                            self.scroll_text(time.trim(), result.pressed_edge);
+                        }
+                        Err(error) => eprintln!("warning: couldn't get the time: {}", error),
+                    }
+                }
+            }
+            /// A rectangle on the 8x8 grid, picked by holding down one corner and pressing the other: it's lit up while both are held,
+            /// and returned (as from `rect_normalized`) when the second is let go. Letting go of the first one instead calls it off.
+            #[track_caller]
+            fn rect_select(&mut self) -> Option<(Key, Key)> {
+                #[derive(Default)]
+                struct Selection {
+                    anchor: Option<Key>,
+                    corner: Option<Key>,
+                }
+                static DATA: CallSiteState<Selection> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let selection = data.entry((self.board, Location::caller())).or_default();
+                let mut selected = None;
+                match self.event {
+                    Event::KeyDown(k) if grid_8x8().any(|key| key == k) => {
+                        if selection.anchor.is_none() {
+                            selection.anchor = Some(k);
+                        } else if selection.corner.is_none() {
+                            selection.corner = Some(k);
+                        }
+                    }
+                    Event::KeyUp(k) if selection.anchor == Some(k) => *selection = Selection::default(),
+                    Event::KeyUp(k) if selection.corner == Some(k) => {
+                        selected = selection.anchor.map(|anchor| rect_normalized(anchor, k));
+                        selection.corner = None;
+                    }
+                    _ => {}
+                }
+                if let (Some(anchor), Some(corner)) = (selection.anchor, selection.corner) {
+                    let (a, b) = rect_normalized(anchor, corner);
+                    for key in rect(a, b) {
+                        self.fb.set(key, Color::simple(3));
                     }
                 }
+                selected
+            }
+            /// A canvas over the rectangle from `region.0` to `region.1`: each key pressed is painted with `color` (so `Color::OFF` rubs out).
+            /// There's no way to tell a drag from separate presses, so that's all painting is, but holding one key down and pressing
+            /// another fills in the rectangle between them (see `rect_select`). Returns everything painted so far.
+            #[track_caller]
+            fn paint_surface(&mut self, region: (Key, Key), color: Color) -> HashMap<Key, Color> {
+                type Painted = HashMap<Key, Color>;
+                static DATA: WidgetState<Painted> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let painted = data.entry((self.board, region.0, Location::caller())).or_default();
+                for key in rect(region.0, region.1) {
+                    if self.event == Event::KeyDown(key) {
+                        if color.is_off() {
+                            painted.remove(&key);
+                        } else {
+                            painted.insert(key, color);
+                        }
+                    }
+                    self.fb.set(key, painted.get(&key).copied().unwrap_or(Color::OFF));
+                }
+                if let Some((a, b)) = self.rect_select() {
+                    let inside = |key| rect(region.0, region.1).any(|k| k == key);
+                    for key in rect(a, b).filter(|key| inside(*key)) {
+                        if color.is_off() {
+                            painted.remove(&key);
+                        } else {
+                            painted.insert(key, color);
+                        }
+                    }
+                    // drawn again, so the filled-in rectangle shows straight away
+                    for key in rect(region.0, region.1) {
+                        self.fb.set(key, painted.get(&key).copied().unwrap_or(Color::OFF));
+                    }
+                }
+                painted.clone()
+            }
+            /// Clears the 8x8 grid and writes `n` across the middle of it in `color`, for as long as it's called.
+            fn number_overlay(&mut self, n: u32, color: Color) {
+                for key in grid_8x8() {
+                    self.fb.set(key, Color::OFF);
+                }
+                let text = n.to_string();
+                let left = 1 + GRID_SIZE.saturating_sub(font::str_width(&text)) / 2;
+                let top = (GRID_SIZE + font::HEIGHT) / 2;
+                font::draw_str(self.fb, &text, left, top, color);
+            }
+            /// A brush picker for `paint_surface`: the palette laid out over the rectangle from `region.0` to `region.1`, with the brush shown on
+            /// `swatch`. Holding a colour down previews it on the swatch, and letting go picks it; tapping the swatch turns the page, if the
+            /// palette doesn't fit. Returns the colour picked most recently.
+            #[track_caller]
+            fn color_picker(&mut self, region: (Key, Key), swatch: Key) -> Color {
+                // the page shown, the colour picked, and the colour being previewed
+                type State = (usize, Color, Option<Color>);
+                static DATA: WidgetState<State> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let (page, picked, preview) = data
+                    .entry((self.board, region.0, Location::caller()))
+                    .or_insert((0, Color::simple(3), None));
+                let keys = rect(region.0, region.1).collect_vec();
+                let pages = (palette::PALETTE.len() - 1) / keys.len() + 1;
+                for (i, key) in keys.iter().enumerate() {
+                    let index = *page * keys.len() + i;
+                    if index >= palette::PALETTE.len() {
+                        self.fb.set(*key, Color::OFF);
+                        continue;
+                    }
+                    let color = Color::simple(index as u8);
+                    match self.press_release_button(*key, color, color) {
+                        Some(true) => *preview = Some(color),
+                        // only if it was pressed on this page
+                        Some(false) if *preview == Some(color) => {
+                            *picked = color;
+                            *preview = None;
+                        }
+                        _ => {}
+                    }
+                }
+                let shown = preview.unwrap_or(*picked);
+                if self.impulse_button(swatch, shown, shown) {
+                    *page = (*page + 1) % pages;
+                    *preview = None;
+                }
+                *picked
             }
             /// A button that returns true once when pressed.
             #[track_caller]
             fn impulse_button(&mut self, key: Key, color: Color, pressed_color: Color) -> bool {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), bool>>> =
+                static DATA: WidgetState<bool> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let pressed = data.entry((key, Location::caller())).or_insert(false);
+                let pressed = data.entry((self.board, key, Location::caller())).or_insert(false);
                 *pressed = match self.event {
                     Event::KeyDown(k) if k == key => true,
                     Event::KeyUp(k) if k == key => false,
                     _ => *pressed,
                 };
-                *self.fb.get_mut(&key).unwrap() = if *pressed { pressed_color } else { color };
+                self.fb.set(key, if *pressed { pressed_color } else { color });
                 if let Event::KeyDown(k) = self.event {
                     k == key
                 } else {
                     false
                 }
             }
+            /// A D-pad on four keys, in the order of `Direction::ALL`, returning which way was pushed. Held directions repeat, like
+            /// `repeat_button`. The keys don't have to be arranged in a cross (`ARROW_KEYS` are in a row).
+            #[track_caller]
+            fn dpad_keys(&mut self, keys: [Key; 4]) -> Option<Direction> {
+                let mut pushed = None;
+                for (key, direction) in keys.into_iter().zip(Direction::ALL) {
+                    if self.repeat_button(
+                        key,
+                        Color::simple(1),
+                        Color::simple(3),
+                        KEY_REPEAT_DELAY,
+                        KEY_REPEAT_INTERVAL,
+                    ) {
+                        pushed = pushed.or(Some(direction));
+                    }
+                }
+                pushed
+            }
+            /// A button that returns true when pressed, and then again every `interval` for as long as it's held, starting after `initial_delay`
+            /// (so that a quick tap only counts once), like a keyboard's key repeat.
+            #[track_caller]
+            fn repeat_button(
+                &mut self,
+                key: Key,
+                color: Color,
+                pressed_color: Color,
+                initial_delay: Duration,
+                interval: Duration,
+            ) -> bool {
+                // when it's next due to repeat, if it's held down
+                type Due = Option<Instant>;
+                static DATA: WidgetState<Due> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let next = data.entry((self.board, key, Location::caller())).or_insert(None);
+                let fire = match (&self.event, *next) {
+                    (&Event::KeyDown(k), _) if k == key => {
+                        *next = Some(Instant::now() + initial_delay);
+                        true
+                    }
+                    (&Event::KeyUp(k), _) if k == key => {
+                        *next = None;
+                        false
+                    }
+                    // counting from now rather than from when it was due, so that it doesn't
+                    // try to catch up after a stall (like an alert) with a burst of repeats
+                    (_, Some(due)) if Instant::now() >= due => {
+                        *next = Some(Instant::now() + interval);
+                        true
+                    }
+                    _ => false,
+                };
+                // nothing else is going to redraw in time for the next repeat
+                if next.is_some() {
+                    self.ticking = true;
+                }
+                self.fb.set(key, if next.is_some() { pressed_color } else { color });
+                fire
+            }
             /// A button that can be pressed, released, or that nothing can happen to.
             #[track_caller]
             fn press_release_button(
@@ -877,28 +2153,102 @@ fn main() -> eyre::Result<()> {
                 color: Color,
                 pressed_color: Color,
             ) -> Option<bool> {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), bool>>> =
+                static DATA: WidgetState<bool> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let pressed = data.entry((key, Location::caller())).or_insert(false);
+                let pressed = data.entry((self.board, key, Location::caller())).or_insert(false);
                 *pressed = match self.event {
                     Event::KeyDown(k) if k == key => true,
                     Event::KeyUp(k) if k == key => false,
                     _ => *pressed,
                 };
-                *self.fb.get_mut(&key).unwrap() = if *pressed { pressed_color } else { color };
+                self.fb.set(key, if *pressed { pressed_color } else { color });
                 match self.event {
                     Event::KeyDown(k) if k == key => Some(true),
                     Event::KeyUp(k) if k == key => Some(false),
                     _ => None,
                 }
             }
-            /// A helper function that returns `true` exactly once each time `val` becomes `true`.
+            /// A combination of keys that returns true once when they are all held down together (in any order), and no others are.
+            fn chord_button(&mut self, keys: &[Key]) -> bool {
+                match self.event {
+                    Event::KeyDown(k) if keys.contains(&k) => {
+                        // count the key that was just pressed, even if it's already been released again
+                        let mut held = self.held.clone();
+                        if !held.contains(&k) {
+                            held.push(k);
+                        }
+                        held.len() == keys.len() && keys.iter().all(|key| held.contains(key))
+                    }
+                    _ => false,
+                }
+            }
+            /// A "you are here" marker: draws over the key for the focused workspace (according to `key`, which gets its number) in `color`.
+            /// Nothing is drawn if no workspace has focus, or if it hasn't got a key.
+            fn focus_indicator(&mut self, key: impl Fn(i32) -> Option<Key>, color: Color) {
+                if let Some(key) = self.workspaces.focused().and_then(|w| key(w.num)) {
+                    self.fb.set(key, color);
+                }
+            }
+            /// Run an i3 command, flashing the pressed button red and reporting the error if it fails (rather than giving up entirely).
+            fn try_i3(&mut self, command: impl AsRef<str>) -> bool {
+                let command = command.as_ref();
+                let error = match self.i3.as_mut().map(|i3| i3.run_command(command)) {
+                    Some(Ok(replies)) => replies
+                        .into_iter()
+                        .find(|r| !r.success)
+                        .map(|r| r.error.unwrap_or_else(|| "unknown error".to_owned())),
+                    Some(Err(e)) => Some(e.to_string()),
+                    None => Some("not connected to i3".to_owned()),
+                };
+                if let Some(error) = error {
+                    self.report_error(&format!("i3 command {:?} failed: {}", command, error));
+                    if let Event::KeyDown(k) = self.event {
+                        self.fb.set(k, Color::flashing(5, 0));
+                    }
+                    false
+                } else {
+                    true
+                }
+            }
+            /// A numeric keypad laid out like a calculator's, 3 keys wide and 4 tall, with `region_start` at the bottom left:
+            /// the bottom row is 0, backspace, and enter. Returns the number entered so far when enter is pressed.
             #[track_caller]
-            fn monostable(&mut self, val: bool, extra_key: u8) -> bool {
-                static DATA: Lazy<Mutex<HashMap<(u8, &Location), bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+            fn number_entry(&mut self, region_start: Key) -> Option<u32> {
+                // the number entered so far, if any digits have been entered
+                type Entry = Option<u32>;
+                static DATA: WidgetState<Entry> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let prev = data.entry((extra_key, Location::caller())).or_insert(val);
+                let n = data.entry((self.board, region_start, Location::caller())).or_insert(None);
+                let mut committed = None;
+                for (i, digit) in [1, 2, 3, 4, 5, 6, 7, 8, 9].into_iter().enumerate() {
+                    let i = i as u8;
+                    let key = Key(region_start.0 + 10 * (i / 3 + 1) + i % 3);
+                    if self.impulse_button(key, Color::simple(1), Color::simple(3)) {
+                        *n = n.unwrap_or(0).checked_mul(10).and_then(|n| n.checked_add(digit)).or(*n);
+                    }
+                }
+                if self.impulse_button(region_start, Color::simple(1), Color::simple(3)) {
+                    *n = n.unwrap_or(0).checked_mul(10).or(*n);
+                }
+                if self.impulse_button(Key(region_start.0 + 1), Color::simple(7), Color::simple(5)) {
+                    *n = n.and_then(|n| if n < 10 { None } else { Some(n / 10) });
+                }
+                // enter is lit up brightly if there's anything to enter
+                let enter_color = if n.is_some() { Color::simple(21) } else { Color::simple(23) };
+                if self.impulse_button(Key(region_start.0 + 2), enter_color, Color::simple(21)) {
+                    committed = n.take();
+                }
+                committed
+            }
+            /// Returns `true` exactly once each time `val` becomes `true`: the same edge as `toggle_button_edge`'s, for something that
+            /// isn't a button, but is still shown on `key` (which keeps the ones drawn in a loop apart, like any other widget).
+            #[track_caller]
+            fn rising_edge(&mut self, key: Key, val: bool) -> bool {
+                static DATA: WidgetState<bool> = Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let prev = data.entry((self.board, key, Location::caller())).or_insert(val);
                 let ret = val && !*prev;
                 *prev = val;
                 ret
@@ -906,56 +2256,161 @@ fn main() -> eyre::Result<()> {
             /// A button that returns whether it is currently held down.
             #[track_caller]
             fn holdable_button(&mut self, key: Key, color: Color, pressed_color: Color) -> bool {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), bool>>> =
+                static DATA: WidgetState<bool> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let pressed = data.entry((key, Location::caller())).or_insert(false);
+                let pressed = data.entry((self.board, key, Location::caller())).or_insert(false);
                 *pressed = match self.event {
                     Event::KeyDown(k) if k == key => true,
                     Event::KeyUp(k) if k == key => false,
                     _ => *pressed,
                 };
-                *self.fb.get_mut(&key).unwrap() = if *pressed { pressed_color } else { color };
+                self.fb.set(key, if *pressed { pressed_color } else { color });
                 *pressed
             }
+            /// A button whose behaviour is chosen by `mode`: the momentary, latching and impulse buttons above all in one.
+            #[track_caller]
+            fn button(
+                &mut self,
+                key: Key,
+                mode: ButtonMode,
+                off_color: Color,
+                on_color: Color,
+            ) -> ButtonResult {
+                // whether it's held down, and whether it's latched on
+                type State = (bool, bool);
+                static DATA: WidgetState<State> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let (held, latched) = data.entry((self.board, key, Location::caller())).or_insert((false, false));
+                let pressed_edge = self.event == Event::KeyDown(key);
+                let released_edge = self.event == Event::KeyUp(key);
+                if pressed_edge {
+                    *held = true;
+                    *latched = !*latched;
+                } else if released_edge {
+                    *held = false;
+                }
+                let is_on = match mode {
+                    ButtonMode::Momentary => *held,
+                    ButtonMode::Latching => *latched,
+                    ButtonMode::Impulse => pressed_edge,
+                };
+                // an impulse is over too quickly to see, so light it up while it's held instead
+                let lit = if mode == ButtonMode::Impulse { *held } else { is_on };
+                self.fb.set(key, if lit { on_color } else { off_color });
+                ButtonResult {
+                    pressed_edge,
+                    released_edge,
+                    is_on,
+                }
+            }
             /// A slider to control LED brightness.
             #[track_caller]
             fn led_slider(&mut self, start: Key) {
-                assert_eq!(start % 10, 1);
-                static DATA: Lazy<Mutex<Option<u8>>> = Lazy::new(|| Mutex::new(None));
-                let mut brightness = DATA.lock();
+                assert_eq!(start.0 % 10, 1);
+                static DATA: Lazy<Mutex<HashMap<Board, Option<u8>>>> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let brightness = data.entry(self.board).or_insert(None);
                 if let Event::Brightness(b) = self.event {
                     *brightness = Some(b);
                 }
                 if brightness.is_none() {
-                    // thread::sleep(Duration::from_millis(2)); // XXX HACK EW EW EW
-                    self.launchpad_for_side_effects
-                        .send(&Command::GetBrightness)
-                        .unwrap();
+                    *brightness = self.launchpad_for_side_effects.query_brightness().ok();
                 }
                 for i in 0..8 {
-                    let color = if brightness.unwrap_or(255) / 16 == i {
+                    let color = if brightness.map(|b| Brightness(b).step()) == Some(i as usize) {
                         Color::Simple(SimpleColor::Static(113))
                     } else {
                         Color::Simple(SimpleColor::Static(104))
                     };
-                    if self.impulse_button(start + i, color, color) {
-                        // the "correct" sequence here is: 0, 18, 36, 54, 72, 91, 109, 127
-                        // integer maths gives 90 and 108, not 91 and 109:
-                        //     (i as u64 * 127 / 7) as u8
-                        // floating-point maths and rounding gives 73, not 72:
-                        //     ((i as f32 * 127. / 7.).round()) as u8
-                        // so we have to bias the result a little by subtracting 0.1 after the division
-                        // hey novation: ????????
-                        let b = (i as f32 * 127. / 7. - 0.1).round() as u8;
+                    if self.impulse_button(Key(start.0 + i), color, color) {
+                        let b = Brightness::from_fraction(i as f32 / 7.);
+                        self.launchpad_for_side_effects
+                            .send(&Command::SetBrightness(b.0))
+                            .unwrap();
+                        *brightness = self.launchpad_for_side_effects.query_brightness().ok();
+                    }
+                }
+            }
+            /// Dims the LEDs to `dim_level` after `idle_timeout` without any key presses, and restores the previous brightness on the next press.
+            /// Returns whether they're dimmed at the moment.
+            fn auto_dim(&mut self, idle_timeout: Duration, dim_level: u8) -> bool {
+                // when the last key was pressed, and the brightness to go back to if we're dimmed
+                type Idle = (Instant, Option<u8>);
+                static DATA: Lazy<Mutex<HashMap<Board, Idle>>> =
+                    Lazy::new(|| Mutex::new(HashMap::new()));
+                let mut data = DATA.lock();
+                let (last_press, bright) = data.entry(self.board).or_insert((Instant::now(), None));
+                if let Event::KeyDown(_) = self.event {
+                    *last_press = Instant::now();
+                    if let Some(b) = bright.take() {
                         self.launchpad_for_side_effects
                             .send(&Command::SetBrightness(b))
                             .unwrap();
+                    }
+                } else if bright.is_none() && last_press.elapsed() >= idle_timeout {
+                    // ask rather than assuming, so that whatever the brightness was last set to
+                    // (e.g. by `led_slider`) is what comes back
+                    if let Ok(b) = self.launchpad_for_side_effects.query_brightness() {
+                        *bright = Some(b);
                         self.launchpad_for_side_effects
-                            .send(&Command::GetBrightness)
+                            .send(&Command::SetBrightness(dim_level))
                             .unwrap();
                     }
                 }
+                bright.is_some()
+            }
+            /// A sustain pedal for `synth`, which latches on and off with each press. Since it's the synth's own state it shows, each tab
+            /// with a keyboard on it can have its own pedal, and they all stay in step.
+            fn sustain_pedal(&mut self, key: Key, synth: &Mutex<AudioState>) {
+                let sustain = synth.lock().sustain;
+                let color = Color::simple(if sustain { 3 } else { 1 });
+                if self.impulse_button(key, color, color) {
+                    synth.lock().set_sustain(!sustain);
+                }
+            }
+            /// A keyboard for `synth`, with a note for each key in `map` (black and white, like a piano's). The notes are numbered from
+            /// `id_base` by key, so each keyboard needs one of its own. `map` can be swapped for another at any time, since anything
+            /// still sounding from a key that's no longer mapped is let go of.
+            #[track_caller]
+            fn keyboard(&mut self, map: &KeyMap, id_base: usize, envelope: Envelope, synth: &Mutex<AudioState>) {
+                for key in grid_full() {
+                    let id = id_base + key.0 as usize;
+                    match map.freqs.get(&key) {
+                        Some(&freq) => {
+                            let (color, pressed_color) = if is_accidental(freq) { (94, 93) } else { (92, 91) };
+                            let held = self.holdable_button(key, Color::simple(color), Color::simple(pressed_color));
+                            synth.lock().play(id, freq, envelope, held);
+                        }
+                        None => {
+                            let mut synth = synth.lock();
+                            if synth.notes.contains_key(&id) {
+                                synth.play(id, 0.0, envelope, false);
+                            }
+                        }
+                    }
+                }
+            }
+            /// An oscilloscope covering the 8x8 grid: `samples` are squashed into eight columns, with -1.0 at the bottom and 1.0 at the top.
+            /// Anything beyond that is drawn in the top or bottom row in red, to show that it's clipping.
+            fn waveform(&mut self, samples: &[f32]) {
+                let chunk = (samples.len() / 8).max(1);
+                for x in 1..=8 {
+                    // just take every nth sample: averaging would cancel out anything but the lowest notes
+                    let sample = samples.get((x as usize - 1) * chunk).copied().unwrap_or(0.0);
+                    let (row, color) = match sample {
+                        s if s > 1.0 => (8, Color::simple(5)),
+                        s if s < -1.0 => (1, Color::simple(5)),
+                        s => (((s + 1.0) / 2.0 * 8.0) as u8 + 1, Color::simple(21)),
+                    };
+                    for y in 1..=8 {
+                        // exactly 1.0 would land just above the top row
+                        let lit = y == row.min(8);
+                        self.fb.set(coords_to_key(Coord::new(x, y)), if lit { color } else { Color::OFF });
+                    }
+                }
             }
             /// A button that quits the application when pressed.
             #[track_caller]
@@ -970,27 +2425,52 @@ fn main() -> eyre::Result<()> {
                 }
             }
             /// A sleep button. Designed to be wrapped around the entire UI; when asleep, reacts to and rewrites any button-press to a plain redraw.
+            /// If `sleep_device` is set, the Launchpad itself is also put to sleep (and woken up again afterwards).
+            /// If `wake_press_acts` is set, the press that wakes the UI isn't rewritten, so it goes on to do whatever it would have done anyway.
             #[track_caller]
-            fn awake(&mut self, key: Key, color: Color) -> bool {
-                static DATA: Lazy<Mutex<HashMap<(Key, &Location), bool>>> =
+            fn awake(
+                &mut self,
+                key: Key,
+                color: Color,
+                sleep_device: bool,
+                wake_press_acts: bool,
+            ) -> bool {
+                static DATA: WidgetState<bool> =
                     Lazy::new(|| Mutex::new(HashMap::new()));
                 let mut data = DATA.lock();
-                let awake = data.entry((key, Location::caller())).or_insert(true);
+                let awake = data.entry((self.board, key, Location::caller())).or_insert(true);
                 *awake = match (*awake, &self.event) {
-                    (true, &Event::KeyDown(k)) if k == key => false,
+                    (true, &Event::KeyDown(k)) if k == key => {
+                        if sleep_device {
+                            self.launchpad_for_side_effects
+                                .send(&Command::SetAwake(false))
+                                .unwrap();
+                        }
+                        false
+                    }
                     (true, _) => *awake,
                     (false, &Event::KeyUp(k)) if k == key => *awake,
                     (false, &Event::KeyDown(_)) => {
-                        self.event = Event::Redraw;
+                        if sleep_device {
+                            self.launchpad_for_side_effects
+                                .send(&Command::SetAwake(true))
+                                .unwrap();
+                            // the device doesn't necessarily remember what it was displaying
+                            self.launchpad_for_side_effects.resync().unwrap();
+                        }
+                        if !wake_press_acts {
+                            self.event = Event::Redraw;
+                        }
                         true
                     }
                     (false, _) => *awake,
                 };
-                *self.fb.get_mut(&key).unwrap() = if *awake {
+                let color = if *awake {
                     color
                 } else {
-                    Color::Simple(SimpleColor::Static(0))
+                    Color::OFF
                 };
+                self.fb.set(key, color);
                 *awake
             }
             #[track_caller]
@@ -999,8 +2479,11 @@ fn main() -> eyre::Result<()> {
                 key: Key,
                 playing_color: Color,
                 paused_color: Color,
-            ) -> eyre::Result<()> {
-                static DATA: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(duct::cmd!("playerctl", "status").unchecked().read().unwrap() == "Playing"));
+            ) {
+                static DATA: Lazy<Mutex<bool>> = Lazy::new(|| {
+                    let status = playerctl(PLAYER, &["status"]).unchecked().read();
+                    Mutex::new(status.is_ok_and(|status| status == "Playing"))
+                });
                 let mut data = DATA.lock();
                 let playing = &mut *data;
                 *playing = match self.event {
@@ -1013,61 +2496,85 @@ fn main() -> eyre::Result<()> {
                     paused_color
                 };
                 if self.impulse_button(key, color, color) {
-                    if *playing {
-                        process::Command::new("playerctl").arg("pause").status()?;
-                    } else {
-                        process::Command::new("playerctl").arg("play").status()?;
+                    let command = if *playing { "pause" } else { "play" };
+                    if let Err(error) = playerctl(PLAYER, &[command]).unchecked().run() {
+                        self.report_error(&format!("playerctl {} failed: {}", command, error));
                     }
                 }
-                Ok(())
             }
         }
+        let held = launchpad.chord();
         let mut ui = Ui {
             fb: &mut fb,
             event,
-            launchpad_for_side_effects: &mut launchpad,
+            board: MAIN_BOARD,
+            launchpad_for_side_effects: launchpad.as_mut(),
             tx_for_side_effects: &tx,
+            held,
+            i3: i3.as_mut(),
+            workspaces: &workspaces,
+            cancel_animation: &cancel_animation,
+            held_output: &mut held_output,
+            ticking: false,
         };
-        if ui.awake(19, Color::Simple(SimpleColor::Static(47))) {
-            let tab = ui.tabs::<4>(95);
+        let idle = ui.auto_dim(IDLE_TIMEOUT, IDLE_BRIGHTNESS);
+        if ui.chord_button(PANIC_CHORD) {
+            // the notes still held down come back on the next frame, but nothing else does
+            let mut state = audio_state.lock();
+            state.notes.clear();
+            state.set_sustain(false);
+            drop(state);
+            ui.launchpad_for_side_effects.send(&Command::ScrollText {
+                loops: None,
+                speed: None,
+                color: None,
+                text: None,
+            })?;
+            TEXT_SCROLLING.lock().remove(&ui.board);
+            ui.launchpad_for_side_effects.clear_all()?;
+        }
+        // whether anything's still moving, so the ticker knows whether to keep going
+        let mut animating = false;
+        if idle {
+            animating = screensaver.step(ui.fb);
+        } else if ui.awake(Key(19), Color::Simple(SimpleColor::Static(47)), true, WAKE_PRESS_ACTS) {
             // if tab == 1 || tab == 2 {
             //     for key in rect(29, 89) {
             //         ui.palette_button(key);
             //     }
             // }
-            match tab {
-                0 => {
+            let tabs: [Tab; 4] = [
+                &mut |ui| {
                     // i3
                     // shift button
-                    let i3_shift = ui.holdable_button(53, Color::simple(2), Color::simple(3));
+                    let i3_shift = ui
+                        .button(Key(53), ButtonMode::Momentary, Color::simple(2), Color::simple(3))
+                        .is_on;
 
-                    // move
-                    if ui.impulse_button(91, Color::simple(1), Color::simple(2)) {
-                        i3.run_command(if i3_shift { "move up" } else { "focus up" })?;
+                    // move. the arrows keep going while they're held, for getting across a row of
+                    // windows quickly
+                    let verb = if i3_shift { "move" } else { "focus" };
+                    if let Some(direction) = ui.dpad_keys(ARROW_KEYS) {
+                        ui.try_i3(format!("{} {}", verb, direction.name()));
                     }
-                    if ui.impulse_button(92, Color::simple(1), Color::simple(2)) {
-                        i3.run_command(if i3_shift { "move down" } else { "focus down" })?;
-                    }
-                    if ui.impulse_button(93, Color::simple(1), Color::simple(2)) {
-                        i3.run_command(if i3_shift { "move left" } else { "focus left" })?;
-                    }
-                    if ui.impulse_button(94, Color::simple(1), Color::simple(2)) {
-                        i3.run_command(if i3_shift {
-                            "move right"
-                        } else {
-                            "focus right"
-                        })?;
-                    }
-                    // workspaces
                     // for workspace_num in output_base..output_base + 5 {
-                    for workspace_num in 0..15 {
+                    for workspace_num in 0..WORKSPACES {
+                        let key = workspace_to_key(workspace_num).unwrap();
                         let color = {
-                            if let Some(w) = w_by_num.get(&(workspace_num as i32)) {
-                                let first_time = ui.monostable(w.urgent, workspace_num);
+                            if let Some(w) = workspaces.by_num.get(&(workspace_num as i32)) {
+                                let first_time = ui.rising_edge(key, w.urgent);
                                 if w.urgent {
                                     // Color::simple(9)
                                     if first_time {
-                                        animations::alert(ui.launchpad_for_side_effects, Some(81 - (workspace_num / 5 * 10) + (workspace_num % 5))).wrap_err("couldn't display alert animation")?;
+                                        // this only starts the notes off, they fade out by themselves
+                                        let mut state = audio_state.lock();
+                                        for (i, freq) in URGENT_CHIME.iter().enumerate() {
+                                            let envelope = Envelope { release: URGENT_CHIME_DURATION };
+                                            let note = state.notes.entry(9100 + i).or_insert_with(|| NoteState::new(*freq, envelope));
+                                            note.volume = 1.0;
+                                        }
+                                        drop(state);
+                                        animations::alert(ui.launchpad_for_side_effects, Some(key), ui.cancel_animation).wrap_err("couldn't display alert animation")?;
                                         ui.tx_for_side_effects.send(Event::Redraw).unwrap();
                                     }
                                     Color::Simple(SimpleColor::Pulsing(9))
@@ -1089,21 +2596,17 @@ fn main() -> eyre::Result<()> {
                                     Color::simple(color)
                                 }
                             } else {
-                                Color::simple(0)
+                                Color::OFF
                             }
                         };
                         // TODO: yuck (specifically, the `as`)
-                        if ui.impulse_button(
-                            81 - (workspace_num / 5 * 10) + (workspace_num % 5),
-                            color,
-                            color,
-                        ) {
-                            match w_by_num.get(&(workspace_num as i32)) {
+                        if ui.impulse_button(key, color, color) {
+                            match workspaces.by_num.get(&(workspace_num as i32)) {
                                 Some(w) if w.focused && w.urgent => {
-                                    i3.run_command("[urgent=latest workspace=__focused__] focus")?;
+                                    ui.try_i3("[urgent=latest workspace=__focused__] focus");
                                 }
                                 _ => {
-                                    i3.run_command(format!(
+                                    ui.try_i3(format!(
                                         "{}workspace number {}",
                                         if i3_shift {
                                             format!(
@@ -1114,14 +2617,22 @@ fn main() -> eyre::Result<()> {
                                             "".to_owned()
                                         },
                                         workspace_num
-                                    ))?;
+                                    ));
                                 }
                             }
                         }
                     }
+                    ui.focus_indicator(
+                        |n| u8::try_from(n).ok().and_then(workspace_to_key),
+                        Color::simple(3),
+                    );
+                    // while this is on, holding a workspace's button shows its number
+                    let show_numbers = ui
+                        .button(Key(48), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
                     // outputs
                     let mut base = 81;
-                    for (i, output) in outputs
+                    for (i, output) in workspaces.outputs
                         .iter()
                         .filter(|o| o.active)
                         .sorted_by(|a, b| a.rect.x.cmp(&b.rect.x))
@@ -1131,7 +2642,6 @@ fn main() -> eyre::Result<()> {
                         assert!(base >= 21);
                         // output
                         // TODO: if there is already another output button held down, do something
-                        static mut CURRENT_OUTPUT_HELD: Option<String> = None;
                         let c = Color::simple(
                             if let Some(w) = &output
                                 .current_workspace
@@ -1139,7 +2649,7 @@ fn main() -> eyre::Result<()> {
                                 .unwrap()
                                 .parse::<i32>()
                                 .ok()
-                                .and_then(|n| w_by_num.get(&n))
+                                .and_then(|n| workspaces.by_num.get(&n))
                             {
                                 let mut hasher = DefaultHasher::new();
                                 w.output.hash(&mut hasher);
@@ -1163,60 +2673,50 @@ fn main() -> eyre::Result<()> {
                                 6 // should never happen?
                             },
                         );
-                        if ui.impulse_button(base + 8, c, c) {
-                            // Safety: still not
+                        if ui.impulse_button(Key(base + 8), c, c) {
                             let new_output = &output.name;
                             let mut preaction = "".to_owned();
-                            if let Some(old_output) = unsafe { &CURRENT_OUTPUT_HELD } {
+                            if let Some(old_output) = ui.held_output.clone() {
                                 // find the workspaces on `old_output`...
-                                let old_output_workspaces = &w_per_o[old_output];
+                                let old_output_workspaces = &workspaces.per_output[&old_output];
                                 // find the workspaces on `new_output`...
-                                let new_output_workspaces = &w_per_o[new_output];
+                                let new_output_workspaces = &workspaces.per_output[new_output];
                                 // and swap them!
-                                i3.run_command(format!(
-                                    "{}, {}, workspace {}, workspace {}",
-                                    old_output_workspaces
-                                        .iter()
-                                        .map(|w| format!(
-                                            "workspace {w}, move workspace to output {new_output}"
-                                        ))
-                                        .join(", "),
-                                    new_output_workspaces
-                                        .iter()
-                                        .map(|w| format!(
-                                            "workspace {w}, move workspace to output {old_output}"
-                                        ))
-                                        .join(", "),
-                                    old_output_workspaces
-                                        .iter()
-                                        .find(|w| w_by_num[*w].visible)
-                                        .unwrap(),
-                                    output.current_workspace.as_ref().unwrap(),
-                                ))?;
+                                let old_visible = old_output_workspaces
+                                    .iter()
+                                    .find(|w| workspaces.by_num[*w].visible)
+                                    .unwrap()
+                                    .to_string();
+                                ui.try_i3(swap_outputs_command(
+                                    &OutputWorkspaces {
+                                        name: &old_output,
+                                        workspaces: old_output_workspaces,
+                                        visible: &old_visible,
+                                    },
+                                    &OutputWorkspaces {
+                                        name: new_output,
+                                        workspaces: new_output_workspaces,
+                                        visible: output.current_workspace.as_ref().unwrap(),
+                                    },
+                                ));
                             } else if i3_shift {
                                 preaction = format!("move container to output {}; ", output.name,);
                             }
-                            i3.run_command(format!("{}focus output {}", preaction, output.name))?;
+                            ui.try_i3(format!("{}focus output {}", preaction, output.name));
                         }
                         if let Event::KeyDown(k) = ui.event {
-                            if k == base + 8 {
-                                // Safety: not
-                                unsafe {
-                                    CURRENT_OUTPUT_HELD = Some(output.name.clone());
-                                }
+                            if k == Key(base + 8) {
+                                *ui.held_output = Some(output.name.clone());
                             }
                         }
                         if let Event::KeyUp(k) = ui.event {
-                            if k == base + 8 {
-                                // Safety: also not
-                                unsafe {
-                                    CURRENT_OUTPUT_HELD = None;
-                                }
+                            if k == Key(base + 8) {
+                                *ui.held_output = None;
                             }
                         }
-                        // for output_num in w_per_o[&output.name].iter() {
+                        // for output_num in workspaces.per_output[&output.name].iter() {
                         //     let color = Color::simple({
-                        //         let w = &w_by_num[output_num];
+                        //         let w = &workspaces.by_num[output_num];
                         //         if w.focused {
                         //             21
                         //         } else if w.urgent {
@@ -1236,19 +2736,7 @@ fn main() -> eyre::Result<()> {
                     }
 
                     // shortcuts
-                    ui.static_color(88, if process::Command::new("lsusb")
-                        .arg("-d")
-                        .arg("17a0:0304")
-                        .stdout(process::Stdio::null())
-                        .status()?.success() { Color::simple(0) }
-                        else if process::Command::new("pactl")
-                            .arg("list")
-                            .arg("short")
-                            .arg("source-outputs")
-                            .output()?
-                            .stdout.is_empty() { Color::simple(9) }
-                        else { Color::flashing(9, 0) }
-                    );
+                    ui.static_color(Key(88), status.microphone_indicator());
                     // ui.static_color(88, Color::simple(
                     //     if process::Command::new("pactl")
                     //         .arg("list")
@@ -1264,26 +2752,29 @@ fn main() -> eyre::Result<()> {
                     //     // Some(false) => simulate_release(&[Alt, KeyX])?,
                     //     None => {}
                     // }
-                    if ui.impulse_button(68, Color::simple(92), Color::simple(92)) {
-                        i3.run_command("exec --no-startup-id i3-workspace-swap")?;
+                    if ui.impulse_button(Key(68), Color::simple(92), Color::simple(92)) {
+                        ui.try_i3("exec --no-startup-id i3-workspace-swap");
                     }
-                    ui.play_pause_button(58, Color::simple(21), Color::simple(23))?;
-                    if ui.impulse_button(51, Color::simple(109), Color::simple(109)) { // was color 61
+                    ui.play_pause_button(Key(58), Color::simple(21), Color::simple(23));
+                    // lock and screen-off need shift held too, so they can't be hit by accident
+                    ui.static_color(Key(51), Color::simple(109)); // was color 61
+                    if ui.chord_button(&[Key(53), Key(51)]) {
                         // simulate_press(&[MetaLeft, ShiftLeft, KeyF])?;
                         // simulate_press(&[Alt, ShiftLeft, KeyF])?;
                         // thread::sleep(Duration::from_millis(10));
                         // simulate_release(&[MetaLeft, ShiftLeft, KeyF])?;
                         // simulate_release(&[Alt, ShiftLeft, KeyF])?;
-                        i3.run_command("exec --no-startup-id lock")?;
+                        ui.try_i3("exec --no-startup-id lock");
                     }
-                    if ui.impulse_button(67, Color::simple(70), Color::simple(71)) {
-                        i3.run_command("exec --no-startup-id iot big-lamp on")?;
+                    if ui.impulse_button(Key(67), Color::simple(70), Color::simple(71)) {
+                        ui.try_i3("exec --no-startup-id iot big-lamp on");
                     }
-                    if ui.impulse_button(57, Color::simple(70), Color::simple(71)) {
-                        i3.run_command("exec --no-startup-id iot big-lamp off")?;
+                    if ui.impulse_button(Key(57), Color::simple(70), Color::simple(71)) {
+                        ui.try_i3("exec --no-startup-id iot big-lamp off");
                     }
-                    if ui.impulse_button(52, Color::simple(110), Color::simple(110)) {
-                        i3.run_command("exec --no-startup-id xset dpms force off")?;
+                    ui.static_color(Key(52), Color::simple(110));
+                    if ui.chord_button(&[Key(53), Key(52)]) {
+                        ui.try_i3("exec --no-startup-id xset dpms force off");
                     }
 
                     // playback bar
@@ -1327,71 +2818,61 @@ fn main() -> eyre::Result<()> {
                     // sample.env_release(0.5);
                     // sample.dis_crunch(0.5);
                     // sample.dis_drive(0.9);
-                    // calculated with rink:
-                    // > 27.5 * (2**3)
-                    // 220  (dimensionless)
-                    // > 220 * ((2 ** (1/12)) ** 0)
-                    // approx. 220  (dimensionless)
-                    // > 220 * ((2 ** (1/12)) ** 1)
-                    // approx. 233.0818  (dimensionless)
-                    // > 220 * ((2 ** (1/12)) ** 2)
-                    // approx. 246.9416  (dimensionless)
-                    // ...and so on up to 12 (= 440)
-                    // white notes: 0, 2, 3, 5, 7, 8, 10:
-                    // 220.0, 246.9416, 261.6255, 293.6647, 329.6275, 349.2282, 391.9954,
-                    // 440.0, 493.8833, 523.2511, 587.3295, 659.2551, 698.4564, 783.9908
-                    // black notes: 1, (gap), 4, 6, (gap), 9, 11:
-                    // 233.0818, None, 277.1826, 311.1269, None, 369.9944, 415.3046,
-                    // 466.1637, None, 554.3652, 622.2539, None, 739.9888, 830.6093
-                    for (i, freq) in //[262, 294, 330, 349, 392, 440, 494, 524]
-                        [261.6255, 293.6647, 329.6275, 349.2282, 391.9954, 440.0, 493.8833, 523.2511]
-                        .into_iter()
-                        .enumerate()
-                    {
-                        if ui.holdable_button(
-                            (i + 11) as Key,
-                            Color::Simple(SimpleColor::Static(92)),
-                            Color::Simple(SimpleColor::Static(91)),
-                        ) {
-                            // sample.osc_frequency(freq);
-                            // mixer.lock().play(sample);
-                            // TODO: replace with cpal thing
-                            // audio_state.lock().active = true;
-                            let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                            x.entry(i + 100).or_insert_with(|| NoteState::new(freq as f32)).input = true;
-                        } else {
-                            let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                            x.entry(i + 100).or_insert_with(|| NoteState::new(freq as f32)).input = false;
+                    // with this on, the piano makes way for a keypad, for going to a workspace by
+                    // number (even one without a button). shift takes the window there too, like it
+                    // does for the workspace buttons
+                    let jumping = ui
+                        .button(Key(38), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
+                    if jumping {
+                        if let Some(n) = ui.number_entry(Key(11)) {
+                            let preaction = if i3_shift {
+                                format!("move container to workspace number {}; ", n)
+                            } else {
+                                "".to_owned()
+                            };
+                            ui.try_i3(format!("{}workspace number {}", preaction, n));
                         }
                     }
-                    for (i, freq) in //[Some(277), Some(311), None, Some(370), Some(415), Some(466)]
-                        [Some(277.1826), Some(311.1269), None, Some(369.9944), Some(415.3046), Some(466.1637)]
-                        .into_iter()
-                        .enumerate()
-                    {
-                        if let Some(freq) = freq {
-                            if ui.holdable_button(
-                                (i + 22) as Key,
-                                Color::Simple(SimpleColor::Static(94)),
-                                Color::Simple(SimpleColor::Static(93)),
-                            )
-                            {
-                                // sample.osc_frequency(freq);
-                                // mixer.lock().play(sample);
-                                // TODO: replace with cpal thing
-                                // audio_state.lock().active = false;
-                                let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                x.entry(i + 200).or_insert_with(|| NoteState::new(freq as f32)).input = true;
-                            } else {
-                                let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                x.entry(i + 200).or_insert_with(|| NoteState::new(freq as f32)).input = false;
-                            }
+                    // the pedal goes first, so that the notes below know whether to let go. while
+                    // the keypad's showing (or if there's nowhere to play), the piano has no keys,
+                    // so anything it was playing stops
+                    ui.sustain_pedal(Key(28), &audio_state);
+                    let piano = if jumping || !has_audio { &no_piano } else { &small_piano };
+                    ui.keyboard(piano, 100, Envelope::SUSTAINED, &audio_state);
+                    // last of all, so that it goes over everything else
+                    if show_numbers {
+                        if let Some(n) = ui.held.iter().find_map(|key| key_to_workspace(*key)) {
+                            ui.number_overlay(n.into(), Color::simple(3));
                         }
                     }
-                }
-                1 => {
-                    let base = u8::try_from(ui.counter_buttons::<2>(93) * 64).unwrap();
-                    for (i, key) in rect(11, 88).enumerate() {
+                    Ok(())
+                },
+                &mut |ui| {
+                    // the palette, or (with 91 on) a canvas to paint on with the brush, which is
+                    // shown on 93. holding one key and pressing another fills in the rectangle
+                    // between them, or with 94 on, rubs it out. with 92 on as well, the grid picks
+                    // the brush instead, turning the page with 93.
+                    let painting = ui
+                        .button(Key(91), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
+                    if painting {
+                        let picking = ui
+                            .button(Key(92), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                            .is_on;
+                        if picking {
+                            brush = ui.color_picker((Key(11), Key(88)), Key(93));
+                        } else {
+                            let erasing = ui
+                                .button(Key(94), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                                .is_on;
+                            ui.static_color(Key(93), brush);
+                            ui.paint_surface((Key(11), Key(88)), if erasing { Color::OFF } else { brush });
+                        }
+                        return Ok(());
+                    }
+                    let base = u8::try_from(ui.counter_buttons::<2>(Key(93)) * 64).unwrap();
+                    for (i, key) in grid_8x8().enumerate() {
                         let color = base + i as u8;
                         ui.info_button(
                             key,
@@ -1399,8 +2880,25 @@ fn main() -> eyre::Result<()> {
                             &(color).to_string(),
                         );
                     }
-                }
-                2 => {
+                    Ok(())
+                },
+                &mut |ui| {
+                    let show_waveform = ui
+                        .button(Key(89), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
+                    let metronome = ui
+                        .button(Key(79), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
+                    METRONOME_RUNNING.store(metronome, Ordering::Relaxed);
+                    if let Event::Beat(n) = ui.event {
+                        let accent = METRONOME_ACCENTS[n];
+                        ui.static_color(Key(69), Color::simple(if accent { 5 } else { 3 }));
+                        // a click is just a note that's struck but not held
+                        let freq = if accent { 1760.0 } else { 880.0 };
+                        let mut state = audio_state.lock();
+                        let click = state.notes.entry(9000 + accent as usize).or_insert_with(|| NoteState::new(freq, Envelope::SHORT));
+                        click.volume = 1.0;
+                    }
                     // for key in rect(11, 88) {
                     //     ui.toggle_button(
                     //         key,
@@ -1408,74 +2906,116 @@ fn main() -> eyre::Result<()> {
                     //         Color::Simple(SimpleColor::Static(20)),
                     //     );
                     // }
-                    for (row, freq_mult) in [0.5, 1.0, 2.0, 4.0].into_iter().enumerate() {
-                        for (i, freq) in //[262, 294, 330, 349, 392, 440, 494, 524]
-                        [261.6255, 293.6647, 329.6275, 349.2282, 391.9954, 440.0, 493.8833, 523.2511]
-                            .into_iter()
-                            .enumerate()
-                        {
-                            if ui.holdable_button(
-                                (i + 11 + (row * 20)) as Key,
-                                Color::Simple(SimpleColor::Static(92)),
-                                Color::Simple(SimpleColor::Static(91)),
-                            ) {
-                                // sample.osc_frequency(freq);
-                                // mixer.lock().play(sample);
-                                // TODO: replace with cpal thing
-                                // audio_state.lock().active = true;
-                                let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                x.entry(i + 1000 + (row * 100)).or_insert_with(|| NoteState::new(freq as f32 * freq_mult)).input = true;
+                    ui.sustain_pedal(Key(49), &audio_state);
+                    // each press moves on to the next timbre, and says which it is
+                    if ui.impulse_button(Key(39), Color::simple(1), Color::simple(3)) {
+                        let mut state = audio_state.lock();
+                        state.timbre = state.timbre.next();
+                        let name = format!("{:?}", state.timbre);
+                        drop(state);
+                        ui.scroll_text(&name, true);
+                    }
+                    // with the in-key layout, every key plays a note of the chosen scale (91 and 92
+                    // pick which) starting from the chosen root (93 and 94), so nothing can be out
+                    // of key. the roots are lit up to find your way around by.
+                    let in_key = ui
+                        .button(Key(59), ButtonMode::Latching, Color::simple(1), Color::simple(3))
+                        .is_on;
+                    // several keys can play the same note, so a note is only let go once none of
+                    // them are held (and anything left over from a different scale, or from before
+                    // the layout was switched off, goes too)
+                    let mut held_notes = Vec::new();
+                    if in_key {
+                        let (scale, scale_changed) =
+                            ui.counter_buttons_edge::<{ Scale::ALL.len() as i64 }>(Key(91));
+                        let (root, root_changed) = ui.counter_buttons_edge::<12>(Key(93));
+                        let scale = Scale::ALL[scale as usize];
+                        if scale_changed || root_changed {
+                            ui.scroll_text(&format!("{} {:?}", NOTE_NAMES[root as usize], scale), true);
+                        }
+                        let lowest = IN_KEY_LOWEST_C + root as u8;
+                        for key in grid_8x8() {
+                            let Coord { x, y } = key_to_coords(key);
+                            let note = scale.note_at(lowest, x, y);
+                            let (color, pressed_color) = if note % 12 == lowest % 12 {
+                                (94, 93)
                             } else {
-                                let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                x.entry(i + 1000 + (row * 100)).or_insert_with(|| NoteState::new(freq as f32 * freq_mult)).input = false;
+                                (92, 91)
+                            };
+                            if ui.holdable_button(key, Color::simple(color), Color::simple(pressed_color)) {
+                                held_notes.push(note);
                             }
                         }
-                        for (i, freq) in //[Some(277), Some(311), None, Some(370), Some(415), Some(466)]
-                        [Some(277.1826), Some(311.1269), None, Some(369.9944), Some(415.3046), Some(466.1637)]
-                            .into_iter()
-                            .enumerate()
-                        {
-                            if let Some(freq) = freq {
-                                if ui.holdable_button(
-                                    (i + 22 + (row * 20)) as Key,
-                                    Color::Simple(SimpleColor::Static(94)),
-                                    Color::Simple(SimpleColor::Static(93)),
-                                )
-                                {
-                                    // sample.osc_frequency(freq);
-                                    // mixer.lock().play(sample);
-                                    // TODO: replace with cpal thing
-                                    // audio_state.lock().active = false;
-                                    let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                    x.entry(i + 2000 + (row * 100)).or_insert_with(|| NoteState::new(freq as f32 * freq_mult)).input = true;
-                                } else {
-                                    let x: &mut HashMap<usize, NoteState> = &mut audio_state.lock().notes;
-                                    x.entry(i + 2000 + (row * 100)).or_insert_with(|| NoteState::new(freq as f32 * freq_mult)).input = false;
-                                }
-                            }
+                    } else {
+                        // 91 and 92 pick the layout instead
+                        let (preset, preset_changed) =
+                            ui.counter_buttons_edge::<{ KeyMap::PRESETS.len() as i64 }>(Key(91));
+                        let (name, map) = &keymaps[preset as usize];
+                        if preset_changed {
+                            ui.scroll_text(name, true);
                         }
+                        ui.keyboard(map, 1000, Envelope::SHORT, &audio_state);
                     }
-                }
-                3 => {
+                    let mut state = audio_state.lock();
+                    for note in 0..=127u8 {
+                        let id = 3000 + note as usize;
+                        let held = held_notes.contains(&note);
+                        if held || state.notes.contains_key(&id) {
+                            state.play(id, note_to_freq(note), Envelope::SHORT, held);
+                        }
+                    }
+                    drop(state);
+                    // this is drawn over the keyboard, which still plays as usual underneath
+                    if show_waveform {
+                        let output = audio_state.lock().output.clone();
+                        ui.waveform(&output);
+                    }
+                    Ok(())
+                },
+                &mut |ui| {
                     // "L", "D"
                     for key in [81, 71, 61, 51, 52, 86, 87, 76, 78, 66, 68, 56, 57] {
-                        ui.static_color(key, Color::Simple(SimpleColor::Static(40)));
+                        ui.static_color(Key(key), Color::Simple(SimpleColor::Static(40)));
                     }
                     // "E"
                     for key in [83, 84, 85, 73, 74, 63, 53, 54, 55] {
-                        ui.static_color(key, Color::Simple(SimpleColor::Static(113)));
+                        ui.static_color(Key(key), Color::Simple(SimpleColor::Static(113)));
                     }
-                    ui.led_slider(31);
-                    ui.exit_button(18);
-                }
-                _ => unreachable!(),
-            }
+                    ui.led_slider(Key(31));
+                    ui.clock(Key(28), Color::simple(1), Color::simple(3), CLOCK_FORMAT);
+                    ui.exit_button(Key(18));
+                    Ok(())
+                },
+            ];
+            // the i3 tab is left out if there's no i3, and the synth tab if there's nowhere to play.
+            // otherwise, the i3 tab shows through behind the synth's keyboard.
+            let present = [ui.i3.is_some(), true, has_audio, true];
+            let position = |i: usize| present[i].then(|| present[..i].iter().filter(|p| **p).count());
+            let (i3_tab, synth_tab) = (position(0), position(2));
+            let ghost_of = |tab| if Some(tab) == synth_tab { i3_tab } else { None };
+            let mut tabs = tabs
+                .into_iter()
+                .zip(present)
+                .filter_map(|(tab, present)| present.then_some(tab))
+                .collect_vec();
+            ui.tab_pages(Key(95), &mut tabs, ghost_of)?;
+            ui.error_indicator();
         }
+        animation_ticker.set_active(animating || ui.ticking);
         // redraw
         launchpad.full_update(&fb)?;
     }
 
-    animations::shutdown(&mut launchpad).wrap_err("couldn't display shutdown animation")?;
+    // whatever signal got us here has already done its cancelling, so only another one should stop
+    // the shutdown animation
+    cancel_animation.store(false, Ordering::Relaxed);
+    if FADE_OUT_ON_SHUTDOWN {
+        animations::fade_out(launchpad.as_mut(), Duration::from_millis(500), &cancel_animation)
+            .wrap_err("couldn't fade out")?;
+    } else {
+        animations::shutdown(launchpad.as_mut(), &cancel_animation)
+            .wrap_err("couldn't display shutdown animation")?;
+    }
 
     Ok(())
 }
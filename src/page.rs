@@ -0,0 +1,129 @@
+// a declarative alternative to drawing the UI by hand: register widgets against their keys once,
+// then feed the page every message and let it draw itself into a framebuffer each frame.
+//
+//     let mut page = Page::new()
+//         .add(Key(91), Widget::Static(Color::simple(3)))
+//         .add(Key(92), Widget::Impulse { color: Color::simple(1), pressed_color: Color::simple(3), on_press: Box::new(|| ...) });
+//     page.handle(&message);
+//     page.draw(&mut fb);
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Color, Framebuffer, Key, Message};
+
+pub enum Widget<'a> {
+    // a colour that never changes
+    Static(Color),
+    // calls `on_press` once each time the key is pressed
+    Impulse {
+        color: Color,
+        pressed_color: Color,
+        on_press: Box<dyn FnMut() + 'a>,
+    },
+    // flips between on and off each time the key is pressed, calling `on_toggle` with the new state
+    Toggle {
+        inactive_color: Color,
+        active_color: Color,
+        on_toggle: Box<dyn FnMut(bool) + 'a>,
+    },
+    // calls `on_change` with true when the key is pressed and false when it's released
+    Hold {
+        color: Color,
+        pressed_color: Color,
+        on_change: Box<dyn FnMut(bool) + 'a>,
+    },
+}
+
+#[derive(Default)]
+pub struct Page<'a> {
+    widgets: HashMap<Key, Widget<'a>>,
+    pressed: HashSet<Key>,
+    toggled: HashSet<Key>,
+}
+
+impl<'a> Page<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a later widget on the same key replaces the earlier one
+    pub fn add(mut self, key: Key, widget: Widget<'a>) -> Self {
+        self.widgets.insert(key, widget);
+        self
+    }
+
+    // returns whether any widget was interested in the message
+    pub fn handle(&mut self, message: &Message) -> bool {
+        let (key, down) = match *message {
+            Message::KeyDown(key) => (key, true),
+            Message::KeyUp(key) => (key, false),
+            _ => return false,
+        };
+        let widget = match self.widgets.get_mut(&key) {
+            Some(widget) => widget,
+            None => return false,
+        };
+        if down {
+            self.pressed.insert(key);
+        } else {
+            self.pressed.remove(&key);
+        }
+        match widget {
+            Widget::Static(_) => {}
+            Widget::Impulse { on_press, .. } => {
+                if down {
+                    on_press();
+                }
+            }
+            Widget::Toggle { on_toggle, .. } => {
+                if down {
+                    let on = !self.toggled.remove(&key);
+                    if on {
+                        self.toggled.insert(key);
+                    }
+                    on_toggle(on);
+                }
+            }
+            Widget::Hold { on_change, .. } => on_change(down),
+        }
+        true
+    }
+
+    // only touches the keys that have widgets on them, so it can be drawn over (or under) by hand
+    pub fn draw(&self, fb: &mut Framebuffer) {
+        for (key, widget) in self.widgets.iter() {
+            let pressed = self.pressed.contains(key);
+            let color = match widget {
+                Widget::Static(color) => *color,
+                Widget::Impulse {
+                    color,
+                    pressed_color,
+                    ..
+                }
+                | Widget::Hold {
+                    color,
+                    pressed_color,
+                    ..
+                } => {
+                    if pressed {
+                        *pressed_color
+                    } else {
+                        *color
+                    }
+                }
+                Widget::Toggle {
+                    inactive_color,
+                    active_color,
+                    ..
+                } => {
+                    if self.toggled.contains(key) {
+                        *active_color
+                    } else {
+                        *inactive_color
+                    }
+                }
+            };
+            fb.set(*key, color);
+        }
+    }
+}
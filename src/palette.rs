@@ -0,0 +1,145 @@
+// approximate sRGB values (0-255) for each entry of the Launchpad's 128-colour palette
+// (transcribed by eye from the programmer's reference, so don't expect an exact match)
+pub const PALETTE: [(u8, u8, u8); 128] = [
+    (0x00, 0x00, 0x00),
+    (0x1e, 0x1e, 0x1e),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0xff, 0xff),
+    (0xff, 0x4c, 0x4c),
+    (0xff, 0x00, 0x00),
+    (0x59, 0x00, 0x00),
+    (0x19, 0x00, 0x00),
+    (0xff, 0xbd, 0x6c),
+    (0xff, 0x54, 0x00),
+    (0x59, 0x1d, 0x00),
+    (0x27, 0x1b, 0x00),
+    (0xff, 0xff, 0x4c),
+    (0xff, 0xff, 0x00),
+    (0x59, 0x59, 0x00),
+    (0x19, 0x19, 0x00),
+    (0x88, 0xff, 0x4c),
+    (0x54, 0xff, 0x00),
+    (0x1d, 0x59, 0x00),
+    (0x14, 0x2b, 0x00),
+    (0x4c, 0xff, 0x4c),
+    (0x00, 0xff, 0x00),
+    (0x00, 0x59, 0x00),
+    (0x00, 0x19, 0x00),
+    (0x4c, 0xff, 0x5e),
+    (0x00, 0xff, 0x19),
+    (0x00, 0x59, 0x0d),
+    (0x00, 0x19, 0x02),
+    (0x4c, 0xff, 0x88),
+    (0x00, 0xff, 0x55),
+    (0x00, 0x59, 0x1d),
+    (0x00, 0x1f, 0x12),
+    (0x4c, 0xff, 0xb7),
+    (0x00, 0xff, 0x99),
+    (0x00, 0x59, 0x35),
+    (0x00, 0x19, 0x12),
+    (0x4c, 0xc3, 0xff),
+    (0x00, 0xa9, 0xff),
+    (0x00, 0x41, 0x52),
+    (0x00, 0x10, 0x19),
+    (0x4c, 0x88, 0xff),
+    (0x00, 0x55, 0xff),
+    (0x00, 0x1d, 0x59),
+    (0x00, 0x08, 0x19),
+    (0x4c, 0x4c, 0xff),
+    (0x00, 0x00, 0xff),
+    (0x00, 0x00, 0x59),
+    (0x00, 0x00, 0x19),
+    (0x87, 0x4c, 0xff),
+    (0x54, 0x00, 0xff),
+    (0x19, 0x00, 0x64),
+    (0x0f, 0x00, 0x30),
+    (0xff, 0x4c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x59, 0x00, 0x59),
+    (0x19, 0x00, 0x19),
+    (0xff, 0x4c, 0x87),
+    (0xff, 0x00, 0x54),
+    (0x59, 0x00, 0x1d),
+    (0x22, 0x00, 0x13),
+    (0xff, 0x15, 0x00),
+    (0x99, 0x35, 0x00),
+    (0x79, 0x51, 0x00),
+    (0x43, 0x64, 0x00),
+    (0x03, 0x39, 0x00),
+    (0x00, 0x57, 0x35),
+    (0x00, 0x54, 0x7f),
+    (0x00, 0x00, 0xff),
+    (0x00, 0x45, 0x4f),
+    (0x25, 0x00, 0xcc),
+    (0x7f, 0x7f, 0x7f),
+    (0x20, 0x20, 0x20),
+    (0xff, 0x00, 0x00),
+    (0xbd, 0xff, 0x2d),
+    (0xaf, 0xed, 0x06),
+    (0x64, 0xff, 0x09),
+    (0x10, 0x8b, 0x00),
+    (0x00, 0xff, 0x87),
+    (0x00, 0xa9, 0xff),
+    (0x00, 0x2a, 0xff),
+    (0x3f, 0x00, 0xff),
+    (0x7a, 0x00, 0xff),
+    (0xb2, 0x1a, 0x7d),
+    (0x40, 0x21, 0x00),
+    (0xff, 0x4a, 0x00),
+    (0x88, 0xe1, 0x06),
+    (0x72, 0xff, 0x15),
+    (0x00, 0xff, 0x00),
+    (0x3b, 0xff, 0x26),
+    (0x59, 0xff, 0x71),
+    (0x38, 0xff, 0xcc),
+    (0x5b, 0x8a, 0xff),
+    (0x31, 0x51, 0xc6),
+    (0x87, 0x7f, 0xe9),
+    (0xd3, 0x1d, 0xff),
+    (0xff, 0x00, 0x5d),
+    (0xff, 0x7f, 0x00),
+    (0xb9, 0xb0, 0x00),
+    (0x90, 0xff, 0x00),
+    (0x83, 0x5d, 0x07),
+    (0x39, 0x2b, 0x00),
+    (0x14, 0x4c, 0x10),
+    (0x0d, 0x50, 0x38),
+    (0x15, 0x15, 0x2a),
+    (0x16, 0x20, 0x5a),
+    (0x69, 0x3c, 0x1c),
+    (0xa8, 0x00, 0x0a),
+    (0xde, 0x51, 0x3d),
+    (0xd8, 0x6a, 0x1c),
+    (0xff, 0xe1, 0x26),
+    (0x9e, 0xe1, 0x2f),
+    (0x67, 0xb5, 0x0f),
+    (0x1e, 0x1e, 0x30),
+    (0xdc, 0xff, 0x6b),
+    (0x80, 0xff, 0xbd),
+    (0x9a, 0x99, 0xff),
+    (0x8e, 0x66, 0xff),
+    (0x40, 0x40, 0x40),
+    (0x75, 0x75, 0x75),
+    (0xe0, 0xff, 0xff),
+    (0xa0, 0x00, 0x00),
+    (0x35, 0x00, 0x00),
+    (0x1a, 0xd0, 0x00),
+    (0x07, 0x42, 0x00),
+    (0xb9, 0xb0, 0x00),
+    (0x3f, 0x31, 0x00),
+    (0xb3, 0x5f, 0x00),
+    (0x4b, 0x15, 0x02),
+];
+
+pub fn to_rgb(index: u8) -> (u8, u8, u8) {
+    PALETTE[(index & 0x7f) as usize]
+}
+
+// the palette entry that looks most like a 0-255 RGB colour
+pub fn nearest(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |(pr, pg, pb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    (0..128).min_by_key(|i| distance(to_rgb(*i))).unwrap()
+}
@@ -0,0 +1,107 @@
+// two Launchpads (or a Launchpad and a `VirtualLaunchpad`, say) driven as one: everything sent
+// to the tee goes to both, for showing the grid somewhere else at the same time.
+//
+// the first device is the one in charge: it's the one whose held keys and brightness are
+// reported, and whose `UpdateReport`s are passed on. if either device has an error, from then on
+// it's left alone and only the other is driven, so that a mirror coming unplugged doesn't stop the
+// real thing (or vice versa). the error goes to `on_failure` rather than being returned, since the
+// other device is still fine; only once both have failed is there an error to return.
+
+use crate::{Command, ConnectionError, Framebuffer, Key, LaunchpadDevice, UpdateReport};
+
+type FailureHandler = Box<dyn FnMut(usize, &ConnectionError)>;
+
+pub struct TeeDevice {
+    devices: [Box<dyn LaunchpadDevice>; 2],
+    failed: [bool; 2],
+    on_failure: Option<FailureHandler>,
+}
+
+impl TeeDevice {
+    pub fn new(first: Box<dyn LaunchpadDevice>, second: Box<dyn LaunchpadDevice>) -> TeeDevice {
+        TeeDevice {
+            devices: [first, second],
+            failed: [false; 2],
+            on_failure: None,
+        }
+    }
+
+    // have `f` called with which device it was (0 or 1) and what went wrong, when one of them
+    // fails and stops being driven
+    pub fn on_failure<T: FnMut(usize, &ConnectionError) + 'static>(&mut self, f: T) {
+        self.on_failure = Some(Box::new(f));
+    }
+
+    // whether each device is still being driven
+    pub fn healthy(&self) -> [bool; 2] {
+        [!self.failed[0], !self.failed[1]]
+    }
+
+    // do `f` to every device that hasn't failed yet, returning what the first of them to manage it
+    // said. an error is only returned if there's no device left that managed it
+    fn each<T>(
+        &mut self,
+        mut f: impl FnMut(&mut dyn LaunchpadDevice) -> Result<T, ConnectionError>,
+    ) -> Result<T, ConnectionError> {
+        let mut result = None;
+        let mut error = None;
+        for (i, (device, failed)) in self
+            .devices
+            .iter_mut()
+            .zip(self.failed.iter_mut())
+            .enumerate()
+        {
+            if *failed {
+                continue;
+            }
+            match f(device.as_mut()) {
+                Ok(value) => {
+                    result.get_or_insert(value);
+                }
+                Err(e) => {
+                    *failed = true;
+                    if let Some(on_failure) = &mut self.on_failure {
+                        on_failure(i, &e);
+                    }
+                    error = Some(e);
+                }
+            }
+        }
+        match (result, error) {
+            (Some(value), _) => Ok(value),
+            (None, Some(e)) => Err(e),
+            (None, None) => Err(ConnectionError::ConnectionError),
+        }
+    }
+
+    // which device is in charge: the second one, once the first has failed
+    fn in_charge(&self) -> Option<usize> {
+        self.failed.iter().position(|failed| !failed)
+    }
+}
+
+impl LaunchpadDevice for TeeDevice {
+    fn send(&mut self, command: &Command) -> Result<(), ConnectionError> {
+        self.each(|device| device.send(command))
+    }
+    fn full_update(&mut self, new: &Framebuffer) -> Result<UpdateReport, ConnectionError> {
+        self.each(|device| device.full_update(new))
+    }
+    fn resync(&mut self) -> Result<(), ConnectionError> {
+        self.each(|device| device.resync())
+    }
+    fn clear_all(&mut self) -> Result<(), ConnectionError> {
+        self.each(|device| device.clear_all())
+    }
+    fn chord(&self) -> Vec<Key> {
+        self.in_charge()
+            .map(|i| self.devices[i].chord())
+            .unwrap_or_default()
+    }
+    // only asked of the device in charge, which should know best; and a query going unanswered
+    // isn't the device's fault, so it's not counted as failing
+    fn query_brightness(&mut self) -> Result<u8, ConnectionError> {
+        let i = self.in_charge().ok_or(ConnectionError::ConnectionError)?;
+        self.devices[i].query_brightness()
+    }
+}
@@ -0,0 +1,219 @@
+// a pretend Launchpad that draws itself in the terminal, for hacking on the UI without hardware
+//
+// the grid is redrawn (with ANSI truecolor escapes) whenever anything changes. keys are pressed
+// by typing their number on stdin: `55` taps key 55, `+55` holds it down, and `-55` releases it.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::{
+    coords_to_key, grid_full, palette, Color, Command, ComplexColor, ConnectionError, Coord,
+    Framebuffer, HeldKeys, Key, LaunchpadDevice, Message, SimpleColor, UpdateReport,
+    MAX_COLORS_PER_FRAME,
+};
+
+type Callback = Arc<Mutex<Box<dyn FnMut(u64, Message) + Send>>>;
+
+pub struct VirtualLaunchpad {
+    callback: Callback,
+    held: Arc<HeldKeys>,
+    start: Instant,
+    current: HashMap<Key, Color>,
+    brightness: u8,
+    awake: bool,
+    text: Option<String>,
+}
+
+impl VirtualLaunchpad {
+    pub fn connect<T: FnMut(u64, Message) + Send + 'static>(
+        callback: T,
+    ) -> Result<VirtualLaunchpad, ConnectionError> {
+        let callback: Callback = Arc::new(Mutex::new(Box::new(callback)));
+        let held = Arc::new(HeldKeys::new());
+        let start = Instant::now();
+        thread::Builder::new()
+            .name("lp virtual input".into())
+            .spawn({
+                let callback = Arc::clone(&callback);
+                let held = Arc::clone(&held);
+                move || {
+                    for line in io::stdin().lock().lines() {
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(_) => break,
+                        };
+                        let line = line.trim();
+                        let (down, up, key) = match line.as_bytes().first() {
+                            Some(b'+') => (true, false, &line[1..]),
+                            Some(b'-') => (false, true, &line[1..]),
+                            _ => (true, true, line),
+                        };
+                        let key = match key.parse().map(Key) {
+                            Ok(key) if grid_full().any(|k| k == key) => key,
+                            _ => continue,
+                        };
+                        let ts = start.elapsed().as_micros() as u64;
+                        let mut callback = callback.lock();
+                        if down {
+                            held.update(&Message::KeyDown(key));
+                            callback(ts, Message::KeyDown(key));
+                        }
+                        if up {
+                            held.update(&Message::KeyUp(key));
+                            callback(ts, Message::KeyUp(key));
+                        }
+                    }
+                }
+            })
+            .map_err(|_| ConnectionError::ConnectionError)?;
+        let mut launchpad = VirtualLaunchpad {
+            callback,
+            held,
+            start,
+            current: HashMap::with_capacity(MAX_COLORS_PER_FRAME),
+            brightness: 127,
+            awake: true,
+            text: None,
+        };
+        for key in grid_full() {
+            launchpad.current.insert(key, Color::OFF);
+        }
+        launchpad.draw();
+        Ok(launchpad)
+    }
+
+    fn reply(&mut self, message: Message) {
+        let ts = self.start.elapsed().as_micros() as u64;
+        (self.callback.lock())(ts, message);
+    }
+
+    fn draw(&self) {
+        let mut out = io::stdout().lock();
+        // clear the screen and home the cursor
+        let _ = write!(out, "\x1b[2J\x1b[H");
+        for y in (1..=9).rev() {
+            for x in 1..=9 {
+                let color = self.current[&coords_to_key(Coord::new(x, y))];
+                let (r, g, b) = if self.awake { to_rgb(color) } else { (0, 0, 0) };
+                // the brightness is 0-127; scale everything down accordingly
+                let scale = |c: u8| (c as u16 * self.brightness as u16 / 127) as u8;
+                let marker = match color {
+                    Color::Simple(SimpleColor::Flashing(_))
+                    | Color::Complex(ComplexColor::Flashing(..)) => "**",
+                    Color::Simple(SimpleColor::Pulsing(_))
+                    | Color::Complex(ComplexColor::Pulsing(_)) => "~~",
+                    _ => "  ",
+                };
+                let _ = write!(
+                    out,
+                    "\x1b[48;2;{};{};{}m{}\x1b[0m",
+                    scale(r),
+                    scale(g),
+                    scale(b),
+                    marker
+                );
+            }
+            let _ = writeln!(out);
+        }
+        if let Some(text) = &self.text {
+            let _ = writeln!(out, "{}", text);
+        }
+        let _ = write!(out, "> ");
+        let _ = out.flush();
+    }
+}
+
+fn to_rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Simple(SimpleColor::Static(c))
+        | Color::Simple(SimpleColor::Flashing(c))
+        | Color::Simple(SimpleColor::Pulsing(c))
+        | Color::Complex(ComplexColor::Static(c))
+        | Color::Complex(ComplexColor::Flashing(c, _))
+        | Color::Complex(ComplexColor::Pulsing(c)) => palette::to_rgb(c),
+        // the device's RGB values are 0-127
+        Color::Complex(ComplexColor::Rgb(r, g, b)) => (r << 1, g << 1, b << 1),
+    }
+}
+
+impl LaunchpadDevice for VirtualLaunchpad {
+    fn send(&mut self, command: &Command) -> Result<(), ConnectionError> {
+        match command {
+            Command::KeyOn(key, color) => {
+                *self.current.get_mut(key).unwrap() = Color::Simple(*color);
+            }
+            Command::KeyOff(key) => {
+                *self.current.get_mut(key).unwrap() = Color::OFF;
+            }
+            Command::SetColors(colors) => {
+                for (key, color) in colors.iter() {
+                    *self.current.get_mut(key).unwrap() = Color::Complex(*color);
+                }
+            }
+            Command::ScrollText { loops, text, .. } => {
+                self.text = text.map(str::to_owned);
+                // there's no scrolling to wait for here, so it's finished straight away
+                if text.is_some() && *loops == Some(false) {
+                    self.reply(Message::TextScrollFinished);
+                }
+            }
+            Command::SetAwake(awake) => self.awake = *awake,
+            Command::GetAwake => self.reply(Message::Awake(self.awake)),
+            Command::SetBrightness(brightness) => self.brightness = *brightness,
+            Command::GetBrightness => self.reply(Message::Brightness(self.brightness)),
+            Command::GetProgrammerMode => self.reply(Message::ProgrammerMode(true)),
+            _ => {}
+        }
+        self.draw();
+        Ok(())
+    }
+
+    fn full_update(&mut self, new: &Framebuffer) -> Result<UpdateReport, ConnectionError> {
+        // there's no MIDI here, so only the cells are worth counting
+        let mut report = UpdateReport::default();
+        for key in grid_full() {
+            let current = self.current.get_mut(&key).unwrap();
+            if *current != new[key] {
+                *current = new[key];
+                report.cells_changed += 1;
+            }
+        }
+        self.draw();
+        Ok(report)
+    }
+
+    fn resync(&mut self) -> Result<(), ConnectionError> {
+        self.draw();
+        Ok(())
+    }
+
+    fn chord(&self) -> Vec<Key> {
+        self.held.iter().collect()
+    }
+
+    fn query_brightness(&mut self) -> Result<u8, ConnectionError> {
+        Ok(self.brightness)
+    }
+
+    fn clear_all(&mut self) -> Result<(), ConnectionError> {
+        for color in self.current.values_mut() {
+            *color = Color::OFF;
+        }
+        self.draw();
+        Ok(())
+    }
+}
+
+impl Drop for VirtualLaunchpad {
+    fn drop(&mut self) {
+        // put the terminal's colours back how they were
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "\x1b[0m");
+        let _ = out.flush();
+    }
+}
@@ -0,0 +1,309 @@
+// every `Command`'s encoding, checked against the Launchpad Mini MK3 programmer's reference
+// (which is where the expected bytes were copied from, not from the code under test)
+
+use lp::{
+    Color, Command, ComplexColor, Key, Layout, LedFeedback, Message, Model, SimpleColor, TextColor,
+};
+
+const HEADER: [u8; 6] = [0xf0, 0x00, 0x20, 0x29, 0x02, 0x0d];
+
+fn sysex(body: &[u8]) -> Vec<u8> {
+    let mut bytes = HEADER.to_vec();
+    bytes.extend_from_slice(body);
+    bytes.push(0xf7);
+    bytes
+}
+
+#[track_caller]
+fn check(command: Command, expected: &[u8]) {
+    assert_eq!(
+        command.to_bytes(),
+        expected,
+        "{:?} should be {:02x?}",
+        command,
+        expected
+    );
+}
+
+#[test]
+fn device_inquiry() {
+    check(Command::GetVersions, &[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]);
+    // and the replies to it, which the Mini MK3 sends with a device ID of 0
+    let reply = |family: [u8; 2], member: [u8; 2]| {
+        let mut bytes = vec![0xf0, 0x7e, 0x00, 0x06, 0x02, 0x00, 0x20, 0x29];
+        bytes.extend_from_slice(&family);
+        bytes.extend_from_slice(&member);
+        bytes.extend_from_slice(&[0, 4, 0, 7, 0xf7]);
+        bytes
+    };
+    assert_eq!(
+        Message::from(&reply([0x13, 0x01], [0x00, 0x00])[..]),
+        Message::ApplicationVersion([0, 4, 0, 7])
+    );
+    assert_eq!(
+        Message::from(&reply([0x13, 0x11], [0x00, 0x00])[..]),
+        Message::BootloaderVersion([0, 4, 0, 7])
+    );
+    assert_eq!(
+        Message::from(&reply([0x23, 0x01], [0x01, 0x00])[..]),
+        Message::DeviceId {
+            family: 0x0123,
+            member: 0x0001,
+            version: [0, 4, 0, 7]
+        }
+    );
+}
+
+#[test]
+fn layouts() {
+    for (layout, byte) in [
+        (Layout::Session, 0x00),
+        (Layout::Drums, 0x04),
+        (Layout::Keys, 0x05),
+        (Layout::User, 0x06),
+        (Layout::Programmer, 0x7f),
+    ] {
+        check(Command::SetLayout(layout), &sysex(&[0x00, byte]));
+        assert_eq!(Layout::try_from(byte), Ok(layout));
+    }
+    check(Command::GetLayout, &sysex(&[0x00]));
+    assert_eq!(Layout::try_from(0x0d), Err(0x0d));
+}
+
+#[test]
+fn programmer_mode() {
+    check(Command::SetProgrammerMode(false), &sysex(&[0x0e, 0x00]));
+    check(Command::SetProgrammerMode(true), &sysex(&[0x0e, 0x01]));
+    check(Command::GetProgrammerMode, &sysex(&[0x0e]));
+}
+
+#[test]
+fn daw_mode() {
+    // there's no command for it, but the Launchpad says when a DAW switches it
+    assert_eq!(Message::from(&sysex(&[0x10, 0x01])[..]), Message::DawMode(true));
+    assert_eq!(Message::from(&sysex(&[0x10, 0x00])[..]), Message::DawMode(false));
+}
+
+#[test]
+fn unknown_messages() {
+    // a CC that isn't a press or a release, a SysEx from some other manufacturer, and one of ours
+    // that doesn't mean anything
+    for bytes in [
+        vec![0xb0, 91, 64],
+        vec![0xf0, 0x7d, 0x01, 0xf7],
+        sysex(&[0x7f, 0x01, 0x02]),
+    ] {
+        assert_eq!(Message::from(&bytes[..]), Message::Unknown(bytes.clone()));
+    }
+}
+
+#[test]
+fn pro_buttons() {
+    // the Pro's scene launch column and track select row, pressed and then released
+    for (bytes, message) in [
+        ([0xb0, 89, 127], Message::Scene(0, true)),
+        ([0xb0, 89, 0], Message::Scene(0, false)),
+        ([0xb0, 19, 127], Message::Scene(7, true)),
+        ([0xb0, 101, 127], Message::Track(0, true)),
+        ([0xb0, 108, 0], Message::Track(7, false)),
+    ] {
+        assert_eq!(Message::parse(Model::ProMk3, &bytes), message);
+    }
+    // the Mini MK3 has ordinary keys there, and the grid is the same on both
+    assert_eq!(
+        Message::parse(Model::MiniMk3, &[0xb0, 89, 127]),
+        Message::KeyDown(Key(89))
+    );
+    assert_eq!(
+        Message::parse(Model::ProMk3, &[0x90, 55, 0]),
+        Message::KeyUp(Key(55))
+    );
+}
+
+#[test]
+fn notes() {
+    // the lighting type is the MIDI channel: 1 for static, 2 for flashing, 3 for pulsing
+    check(Command::KeyOn(Key(11), SimpleColor::Static(5)), &[0x90, 11, 5]);
+    check(Command::KeyOn(Key(45), SimpleColor::Flashing(21)), &[0x91, 45, 21]);
+    check(Command::KeyOn(Key(99), SimpleColor::Pulsing(53)), &[0x92, 99, 53]);
+    check(Command::KeyOff(Key(88)), &[0x90, 88, 0]);
+    // and the same, by hand
+    check(
+        Command::NoteOn {
+            channel: 2,
+            note: 99,
+            velocity: 53,
+        },
+        &[0x92, 99, 53],
+    );
+    check(
+        Command::NoteOn {
+            channel: 15,
+            note: 0,
+            velocity: 127,
+        },
+        &[0x9f, 0, 127],
+    );
+    check(Command::NoteOff { channel: 3, note: 36 }, &[0x83, 36, 0]);
+}
+
+#[test]
+fn batching() {
+    // a whole frame sends static colours in the SysEx, but a pulsing one (like an urgent
+    // workspace's) has to stay a note on channel 3, or it stops pulsing
+    assert_eq!(
+        Color::Simple(SimpleColor::Static(9)).batch_equivalent(),
+        Some(ComplexColor::Static(9))
+    );
+    assert_eq!(Color::Simple(SimpleColor::Pulsing(9)).batch_equivalent(), None);
+    assert_eq!(Color::Simple(SimpleColor::Flashing(9)).batch_equivalent(), None);
+    check(Command::KeyOn(Key(61), SimpleColor::Pulsing(9)), &[0x92, 61, 9]);
+    // complex colours were going in the SysEx anyway
+    assert_eq!(
+        Color::Complex(ComplexColor::Pulsing(9)).batch_equivalent(),
+        Some(ComplexColor::Pulsing(9))
+    );
+}
+
+#[test]
+#[should_panic]
+fn note_on_channel_out_of_range() {
+    Command::NoteOn {
+        channel: 16,
+        note: 11,
+        velocity: 5,
+    }
+    .to_bytes();
+}
+
+#[test]
+fn timing_clock() {
+    // a realtime message, so no channel and no data
+    check(Command::TimingClock, &[0xf8]);
+}
+
+#[test]
+fn set_colors() {
+    check(
+        Command::SetColors(&[
+            (Key(11), ComplexColor::Static(5)),
+            // colour B comes before colour A
+            (Key(12), ComplexColor::Flashing(6, 7)),
+            (Key(13), ComplexColor::Pulsing(8)),
+            (Key(14), ComplexColor::Rgb(127, 64, 0)),
+        ]),
+        &sysex(&[
+            0x03, //
+            0, 11, 5, //
+            1, 12, 6, 7, //
+            2, 13, 8, //
+            3, 14, 127, 64, 0,
+        ]),
+    );
+    check(Command::SetColors(&[]), &sysex(&[0x03]));
+}
+
+#[test]
+fn scroll_text() {
+    // with nothing at all, it stops whatever's scrolling
+    check(
+        Command::ScrollText {
+            loops: None,
+            speed: None,
+            color: None,
+            text: None,
+        },
+        &sysex(&[0x07]),
+    );
+    // each field can only be given if all the ones before it are
+    check(
+        Command::ScrollText {
+            loops: Some(true),
+            speed: None,
+            color: None,
+            text: None,
+        },
+        &sysex(&[0x07, 0x01]),
+    );
+    check(
+        Command::ScrollText {
+            loops: Some(false),
+            speed: Some(15),
+            color: None,
+            text: None,
+        },
+        &sysex(&[0x07, 0x00, 15]),
+    );
+    check(
+        Command::ScrollText {
+            loops: Some(false),
+            speed: Some(15),
+            color: Some(TextColor::Palette(3)),
+            text: Some("hi"),
+        },
+        &sysex(&[0x07, 0x00, 15, 0x00, 3, b'h', b'i']),
+    );
+    check(
+        Command::ScrollText {
+            loops: Some(true),
+            speed: Some(7),
+            color: Some(TextColor::Rgb(127, 0, 64)),
+            text: Some("hi"),
+        },
+        &sysex(&[0x07, 0x01, 7, 0x01, 127, 0, 64, b'h', b'i']),
+    );
+}
+
+#[test]
+#[should_panic]
+fn scroll_text_speed_without_loops() {
+    Command::ScrollText {
+        loops: None,
+        speed: Some(15),
+        color: None,
+        text: None,
+    }
+    .to_bytes();
+}
+
+#[test]
+#[should_panic]
+fn scroll_text_text_without_color() {
+    Command::ScrollText {
+        loops: Some(false),
+        speed: Some(15),
+        color: None,
+        text: Some("hi"),
+    }
+    .to_bytes();
+}
+
+#[test]
+fn brightness_and_sleep() {
+    check(Command::SetBrightness(127), &sysex(&[0x08, 127]));
+    check(Command::GetBrightness, &sysex(&[0x08]));
+    // 0 is asleep, 1 is awake
+    check(Command::SetAwake(false), &sysex(&[0x09, 0x00]));
+    check(Command::SetAwake(true), &sysex(&[0x09, 0x01]));
+    check(Command::GetAwake, &sysex(&[0x09]));
+}
+
+#[test]
+fn led_feedback() {
+    let internal = LedFeedback {
+        internal: true,
+        external: false,
+    };
+    let external = LedFeedback {
+        internal: false,
+        external: true,
+    };
+    check(Command::SetLedFeedback(internal), &sysex(&[0x0a, 0x01, 0x00]));
+    check(Command::SetLedFeedback(external), &sysex(&[0x0a, 0x00, 0x01]));
+    check(Command::GetLedFeedback, &sysex(&[0x0a]));
+    // and the reply, in the same order
+    let reply = sysex(&[0x0a, 0x00, 0x01]);
+    assert_eq!(LedFeedback::try_from(&reply[..]), Ok(external));
+    assert_eq!(Message::from(&reply[..]), Message::LedFeedback(external));
+    assert_eq!(LedFeedback::try_from(&sysex(&[0x09, 0x01])[..]), Err(()));
+}
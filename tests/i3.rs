@@ -0,0 +1,53 @@
+use lp::{grid_full, Key};
+use lp::i3::{
+    key_to_workspace, swap_outputs_command, workspace_to_key, OutputWorkspaces, WORKSPACES,
+};
+
+#[test]
+fn workspace_keys() {
+    // the formula this replaced, from when it was written out by hand
+    for n in 0..WORKSPACES {
+        assert_eq!(workspace_to_key(n), Some(Key(81 - (n / 5 * 10) + (n % 5))));
+    }
+    assert_eq!(workspace_to_key(WORKSPACES), None);
+}
+
+#[test]
+fn round_trip() {
+    for n in 0..WORKSPACES {
+        assert_eq!(key_to_workspace(workspace_to_key(n).unwrap()), Some(n));
+    }
+    // and every other key is left alone
+    for key in grid_full() {
+        if let Some(n) = key_to_workspace(key) {
+            assert_eq!(workspace_to_key(n), Some(key));
+        }
+    }
+    assert_eq!(
+        grid_full().filter_map(key_to_workspace).count(),
+        WORKSPACES as usize
+    );
+}
+
+#[test]
+fn swapping_outputs() {
+    // two monitors, with 1 and 3 on the left (showing 3) and 2 on the right
+    let left = OutputWorkspaces {
+        name: "DP-1",
+        workspaces: &[1, 3],
+        visible: "3",
+    };
+    let right = OutputWorkspaces {
+        name: "HDMI-1",
+        workspaces: &[2],
+        visible: "2",
+    };
+    // holding down the left one's button, then pressing the right one's
+    assert_eq!(
+        swap_outputs_command(&left, &right),
+        "workspace 1, move workspace to output HDMI-1, \
+         workspace 3, move workspace to output HDMI-1, \
+         workspace 2, move workspace to output DP-1, \
+         workspace 3, workspace 2"
+    );
+}
@@ -0,0 +1,200 @@
+use lp::{
+    bounding_box, coords_to_key, coords_to_key_topleft, decode_key, device_key_to_logical,
+    grid_8x8, grid_full, is_valid_key, key_to_coords, key_to_coords_topleft, logical_to_device_key,
+    rect, rect_normalized, Coord, Key, KeyInfo, Model, Region,
+};
+
+#[test]
+fn decoding() {
+    for (key, coord, region) in [
+        (Key(11), Coord::new(1, 1), Region::Grid),
+        (Key(55), Coord::new(5, 5), Region::Grid),
+        (Key(88), Coord::new(8, 8), Region::Grid),
+        (Key(19), Coord::new(9, 1), Region::Side),
+        (Key(91), Coord::new(1, 9), Region::Top),
+        (Key(99), Coord::new(9, 9), Region::Logo),
+    ] {
+        assert_eq!(
+            decode_key(key),
+            Some(KeyInfo { coord, region }),
+            "key {}",
+            key
+        );
+    }
+    for key in [0, 10, 20, 100].map(Key) {
+        assert_eq!(decode_key(key), None, "key {}", key);
+    }
+}
+
+#[test]
+fn valid_keys() {
+    // the 8x8 grid, the top row, the side column, and the logo: 81 in all
+    let valid: Vec<_> = (0..=99).map(Key).filter(|key| is_valid_key(*key)).collect();
+    assert_eq!(valid.len(), 81);
+    assert_eq!(valid, grid_full().collect::<Vec<_>>());
+    // nothing along the bottom or down the left
+    for key in [0, 1, 9, 10, 20, 50, 90, 100, 255].map(Key) {
+        assert!(!is_valid_key(key), "key {}", key);
+    }
+}
+
+#[test]
+fn grid_sizes() {
+    assert_eq!(grid_8x8().count(), 64);
+    assert_eq!(grid_full().count(), 81);
+    // the 8x8 grid is all of `grid_full` except the top row and side column
+    assert!(grid_8x8().all(|key| key < Key(89) && key.0 % 10 != 9));
+}
+
+#[test]
+fn topleft_coords() {
+    for ((x, y), key) in [
+        ((0, 0), Key(81)),
+        ((7, 0), Key(88)),
+        ((0, 7), Key(11)),
+        ((7, 7), Key(18)),
+        ((8, 0), Key(89)),
+    ] {
+        assert_eq!(coords_to_key_topleft(x, y), key, "({}, {})", x, y);
+        assert_eq!(key_to_coords_topleft(key), Some((x, y)), "key {}", key);
+    }
+    // the top row is off the top
+    for key in (91..=99).map(Key) {
+        assert_eq!(key_to_coords_topleft(key), None, "key {}", key);
+    }
+    for key in [0, 10, 100].map(Key) {
+        assert_eq!(key_to_coords_topleft(key), None, "key {}", key);
+    }
+}
+
+#[test]
+fn coords_agree() {
+    // everything but the top row can be reached both ways, which differ by the flip
+    for key in grid_full().filter(|key| *key < Key(91)) {
+        let Coord { x, y } = key_to_coords(key);
+        assert_eq!(
+            key_to_coords_topleft(key),
+            Some((x - 1, 8 - y)),
+            "key {}",
+            key
+        );
+        assert_eq!(
+            coords_to_key_topleft(x - 1, 8 - y),
+            coords_to_key(Coord::new(x, y))
+        );
+    }
+}
+
+#[test]
+fn normalized_rects() {
+    // any two opposite corners, either way round, come out bottom left then top right
+    for (a, b) in [(23, 57), (57, 23), (27, 53), (53, 27)] {
+        assert_eq!(
+            rect_normalized(Key(a), Key(b)),
+            (Key(23), Key(57)),
+            "{} and {}",
+            a,
+            b
+        );
+    }
+    assert_eq!(rect_normalized(Key(44), Key(44)), (Key(44), Key(44)));
+    let (a, b) = rect_normalized(Key(88), Key(11));
+    assert_eq!(rect(a, b).count(), 64);
+}
+
+#[test]
+fn device_keys() {
+    // the corners of the grid, the ends of the side column and top row, and the logo, as each
+    // model numbers them
+    let corners = [11, 18, 81, 88, 19, 89, 91, 98, 99].map(Key);
+    for model in [Model::MiniMk3, Model::X, Model::ProMk3] {
+        for key in corners {
+            assert_eq!(
+                logical_to_device_key(model, key),
+                Some(key.0),
+                "{:?} {}",
+                model,
+                key
+            );
+            assert_eq!(
+                device_key_to_logical(model, key.0),
+                Some(key),
+                "{:?} {}",
+                model,
+                key
+            );
+        }
+        for raw in [0, 10, 20, 100] {
+            assert_eq!(
+                device_key_to_logical(model, raw),
+                None,
+                "{:?} {}",
+                model,
+                raw
+            );
+        }
+    }
+    // 16 to a row from the top left, with the side button at the end of each row
+    for (key, raw) in [
+        (81, Some(0)),
+        (88, Some(7)),
+        (89, Some(8)),
+        (11, Some(112)),
+        (18, Some(119)),
+        (19, Some(120)),
+        // the top row are CCs, and there's no logo
+        (91, None),
+        (98, None),
+        (99, None),
+    ] {
+        assert_eq!(
+            logical_to_device_key(Model::MiniMk2, Key(key)),
+            raw,
+            "key {}",
+            key
+        );
+        if let Some(raw) = raw {
+            assert_eq!(
+                device_key_to_logical(Model::MiniMk2, raw),
+                Some(Key(key)),
+                "raw {}",
+                raw
+            );
+        }
+    }
+    // past the side button, or below the bottom row
+    for raw in [9, 15, 121, 128] {
+        assert_eq!(
+            device_key_to_logical(Model::MiniMk2, raw),
+            None,
+            "raw {}",
+            raw
+        );
+    }
+    // everything that has a device key comes back to where it started
+    for model in [Model::MiniMk3, Model::X, Model::ProMk3, Model::MiniMk2] {
+        for key in grid_full() {
+            if let Some(raw) = logical_to_device_key(model, key) {
+                assert_eq!(
+                    device_key_to_logical(model, raw),
+                    Some(key),
+                    "{:?} {}",
+                    model,
+                    key
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn bounding_boxes() {
+    // scattered about, with none of them on the corners of the box
+    let keys = [35, 62, 27, 54, 46].map(Key);
+    assert_eq!(bounding_box(keys.into_iter()), Some((Key(22), Key(67))));
+    assert_eq!(
+        bounding_box([Key(44)].into_iter()),
+        Some((Key(44), Key(44)))
+    );
+    assert_eq!(bounding_box(std::iter::empty()), None);
+}
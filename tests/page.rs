@@ -0,0 +1,86 @@
+use std::cell::{Cell, RefCell};
+
+use lp::page::{Page, Widget};
+use lp::{Color, Framebuffer, Key, Message};
+
+const OFF: Color = Color::OFF;
+const ON: Color = Color::simple(3);
+
+#[test]
+fn impulse() {
+    let presses = Cell::new(0);
+    let mut page = Page::new().add(
+        Key(91),
+        Widget::Impulse {
+            color: OFF,
+            pressed_color: ON,
+            on_press: Box::new(|| presses.set(presses.get() + 1)),
+        },
+    );
+    let mut fb = Framebuffer::new();
+    assert!(page.handle(&Message::KeyDown(Key(91))));
+    page.draw(&mut fb);
+    assert_eq!(fb.get(Key(91)), ON);
+    assert!(page.handle(&Message::KeyUp(Key(91))));
+    page.draw(&mut fb);
+    assert_eq!(fb.get(Key(91)), OFF);
+    page.handle(&Message::KeyDown(Key(91)));
+    page.handle(&Message::KeyUp(Key(91)));
+    // keys without a widget aren't anyone's business
+    assert!(!page.handle(&Message::KeyDown(Key(92))));
+    drop(page);
+    assert_eq!(presses.get(), 2);
+}
+
+#[test]
+fn toggle() {
+    let toggles = RefCell::new(Vec::new());
+    let mut page = Page::new().add(
+        Key(11),
+        Widget::Toggle {
+            inactive_color: OFF,
+            active_color: ON,
+            on_toggle: Box::new(|on| toggles.borrow_mut().push(on)),
+        },
+    );
+    let mut fb = Framebuffer::new();
+    for expected in [ON, OFF, ON] {
+        page.handle(&Message::KeyDown(Key(11)));
+        page.draw(&mut fb);
+        assert_eq!(fb.get(Key(11)), expected);
+        // letting go doesn't change anything
+        page.handle(&Message::KeyUp(Key(11)));
+        page.draw(&mut fb);
+        assert_eq!(fb.get(Key(11)), expected);
+    }
+    drop(page);
+    assert_eq!(toggles.into_inner(), [true, false, true]);
+}
+
+#[test]
+fn hold() {
+    let changes = RefCell::new(Vec::new());
+    let mut page = Page::new()
+        .add(
+            Key(19),
+            Widget::Hold {
+                color: OFF,
+                pressed_color: ON,
+                on_change: Box::new(|down| changes.borrow_mut().push(down)),
+            },
+        )
+        .add(Key(29), Widget::Static(Color::simple(5)));
+    let mut fb = Framebuffer::new();
+    // drawing leaves the keys without widgets alone
+    fb.set(Key(55), ON);
+    page.handle(&Message::KeyDown(Key(19)));
+    page.draw(&mut fb);
+    assert_eq!(fb.get(Key(19)), ON);
+    assert_eq!(fb.get(Key(29)), Color::simple(5));
+    assert_eq!(fb.get(Key(55)), ON);
+    page.handle(&Message::KeyUp(Key(19)));
+    page.draw(&mut fb);
+    assert_eq!(fb.get(Key(19)), OFF);
+    drop(page);
+    assert_eq!(changes.into_inner(), [true, false]);
+}